@@ -0,0 +1,267 @@
+//! Attest typed claims over objects, such as code reviews or CI runs.
+//!
+//! An attestation is a signature over a JSON statement blob, naming the
+//! type of the claim, the attested object, the signer and the time at
+//! which the claim was made, along with an optional JSON payload. It is
+//! stored under `refs/signify/attestations/<type>/<signer>/<oid>`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use git2::{Oid, Reference, Repository};
+use serde::{Deserialize, Serialize};
+
+use crate::output::{self, Format, KeyInfo, ObjectInfo};
+use crate::raw::sign::sign;
+use crate::utils::{self, PrivateKey, PublicKey, TreeSignature};
+
+/// Statement signed by an attestation.
+#[derive(Serialize, Deserialize)]
+struct Statement {
+    /// The type of the claim, e.g. `code-reviewed`.
+    #[serde(rename = "type")]
+    kind: String,
+    /// The attested object.
+    object: String,
+    /// Fingerprint of the signer.
+    signer: String,
+    /// Seconds since the epoch at which the claim was made.
+    time: u64,
+    /// Arbitrary details about the claim.
+    payload: Option<serde_json::Value>,
+}
+
+/// Attestation made by the `attest` command, as described in JSON
+/// output.
+#[derive(Serialize)]
+struct Attestation {
+    /// The key that made the attestation.
+    key: KeyInfo,
+    /// The attested object.
+    object: ObjectInfo,
+    /// The reference the attestation is stored under.
+    attestation_ref: String,
+    /// Whether the attestation was created, replaced, or already
+    /// existed.
+    status: AttestStatus,
+}
+
+/// Outcome of attesting with a key.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum AttestStatus {
+    /// A new attestation was created.
+    Created,
+    /// A valid attestation of the same type by the key already
+    /// existed.
+    Exists,
+    /// An attestation of the same type by the key already existed,
+    /// and was replaced.
+    Replaced,
+}
+
+/// Execute the `attest` command. Attests the claim `kind` over `rev`
+/// with every key at `key_path`, along with the JSON read from
+/// `payload_path`. Existing attestations are only replaced if `force`
+/// is set, and must verify otherwise.
+pub fn command(
+    key_path: PathBuf,
+    kind: String,
+    payload_path: Option<PathBuf>,
+    force: bool,
+    rev: String,
+    format: Format,
+) -> Result<()> {
+    check_kind(&kind)?;
+    let repo = utils::open_repository()?;
+    let object_oid = repo
+        .revparse_single(&rev)
+        .with_context(|| format!("Failed to look-up revision {rev}"))?
+        .id();
+    let payload = payload_path.as_deref().map(read_payload).transpose()?;
+    let secret_keys = utils::get_secret_keys(key_path)?;
+    anyhow::ensure!(!secret_keys.is_empty(), "No secret keys to attest with");
+
+    let mut attestations = Vec::new();
+    for (path, secret_key) in secret_keys {
+        let public_key = secret_key.public_key()?;
+        let signer = public_key.fingerprint()?;
+        let reference = craft_attestation_reference(&kind, signer, object_oid);
+
+        let existing = repo.refname_to_id(&reference).ok();
+        if let (Some(existing), false) = (existing, force) {
+            // a forged or stale ref would otherwise hide the lack of a
+            // valid attestation
+            verify_attestation(&repo, &public_key, existing, &kind, signer, object_oid)
+                .with_context(|| {
+                    format!(
+                        "Existing attestation {reference} does not verify, \
+                         pass --force to replace it"
+                    )
+                })?;
+        }
+        let exists = existing.is_some();
+        let status = if exists && !force {
+            AttestStatus::Exists
+        } else {
+            let statement = Statement {
+                kind: kind.clone(),
+                object: object_oid.to_string(),
+                signer: signer.to_string(),
+                time: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .context("System clock is set before the epoch")?
+                    .as_secs(),
+                payload: payload.clone(),
+            };
+            let attestation_oid = attest(&repo, &secret_key, &statement)?;
+            repo.reference(
+                &reference,
+                attestation_oid,
+                force,
+                &format!("git-signify attest: add {kind} attestation"),
+            )
+            .context("Failed to store reference to attestation")?;
+            if exists {
+                AttestStatus::Replaced
+            } else {
+                AttestStatus::Created
+            }
+        };
+
+        if !format.is_json() {
+            match status {
+                AttestStatus::Created => println!("Attested {kind} with key:"),
+                AttestStatus::Exists => println!("Attestation {kind} already exists with key:"),
+                AttestStatus::Replaced => println!("Replaced attestation {kind} with key:"),
+            }
+            println!("  - {}", path.display());
+            println!("Attestation stored under:");
+            println!("  - {reference}");
+        }
+        attestations.push(Attestation {
+            key: KeyInfo::new(path, &public_key)?,
+            object: ObjectInfo::new(&repo, object_oid),
+            attestation_ref: reference,
+            status,
+        });
+    }
+
+    if format.is_json() {
+        output::print_json("attest", &attestations)?;
+    }
+
+    Ok(())
+}
+
+/// Craft a git reference to an attestation of type `kind` by the key
+/// with the given fingerprint, over `object`.
+pub fn craft_attestation_reference(kind: &str, signer: Oid, object: Oid) -> String {
+    format!(
+        "{}{kind}/{signer}/{object}",
+        utils::ALL_SIGNIFY_ATTESTATION_REFS_PREFIX
+    )
+}
+
+/// Find the keys among `public_keys` which attested the claim `kind`
+/// over `object_oid`. `lookup` resolves attestation references, such
+/// that hooks may consider attestations which are being pushed.
+pub fn attested_by<'keys>(
+    repo: &Repository,
+    public_keys: &'keys BTreeMap<PathBuf, PublicKey>,
+    kind: &str,
+    object_oid: Oid,
+    lookup: impl Fn(&str) -> Option<Oid>,
+) -> Result<Vec<&'keys Path>> {
+    let mut attesters = Vec::new();
+
+    for (path, public_key) in public_keys {
+        for signer in public_key.equivalent_fingerprints()? {
+            let reference = craft_attestation_reference(kind, signer, object_oid);
+            let Some(attestation_oid) = lookup(&reference) else {
+                continue;
+            };
+            if verify_attestation(repo, public_key, attestation_oid, kind, signer, object_oid)
+                .is_ok()
+            {
+                attesters.push(path.as_path());
+                break;
+            }
+        }
+    }
+
+    Ok(attesters)
+}
+
+/// Verify the attestation `attestation_oid` by `public_key`, and check
+/// that its statement makes the claim `kind` by `signer` over
+/// `object_oid`.
+fn verify_attestation(
+    repo: &Repository,
+    public_key: &PublicKey,
+    attestation_oid: Oid,
+    kind: &str,
+    signer: Oid,
+    object_oid: Oid,
+) -> Result<()> {
+    let tree_sig = TreeSignature::load_oid(repo, attestation_oid)?;
+    let statement_oid = tree_sig.dereference()?;
+    tree_sig.verify(public_key)?;
+
+    let statement = repo
+        .find_blob(statement_oid)
+        .context("Attestation statement is not a blob")?;
+    let statement: Statement = serde_json::from_slice(statement.content())
+        .context("Failed to parse attestation statement")?;
+
+    anyhow::ensure!(
+        statement.kind == kind,
+        "Attestation claims {}, rather than {kind}",
+        statement.kind
+    );
+    anyhow::ensure!(
+        statement.object == object_oid.to_string(),
+        "Attestation is over {}, rather than {object_oid}",
+        statement.object
+    );
+    anyhow::ensure!(
+        statement.signer == signer.to_string(),
+        "Attestation is by {}, rather than {signer}",
+        statement.signer
+    );
+
+    Ok(())
+}
+
+/// Store `statement` in `repo`, sign it with `secret_key`, and return
+/// the object id of the resulting signature.
+fn attest(repo: &Repository, secret_key: &PrivateKey, statement: &Statement) -> Result<Oid> {
+    let statement =
+        serde_json::to_vec(statement).context("Failed to serialize attestation statement")?;
+    let statement_oid = repo
+        .blob(&statement)
+        .context("Failed to write statement to the object store")?;
+    sign(repo, secret_key, &statement_oid.to_string())
+}
+
+/// Check that `kind` may be used as a component of reference names.
+fn check_kind(kind: &str) -> Result<()> {
+    anyhow::ensure!(
+        !kind.is_empty()
+            && !kind.contains('/')
+            && Reference::is_valid_name(&format!("refs/{kind}")),
+        "Invalid attestation type {kind:?}"
+    );
+    Ok(())
+}
+
+/// Read the JSON payload of an attestation from `path`.
+fn read_payload(path: &Path) -> Result<serde_json::Value> {
+    let payload =
+        fs::read(path).with_context(|| format!("Failed to read payload {}", path.display()))?;
+    serde_json::from_slice(&payload)
+        .with_context(|| format!("Payload {} is not valid JSON", path.display()))
+}
@@ -0,0 +1,162 @@
+//! Read git-signify settings from git config.
+
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use git2::{ErrorCode, Repository};
+
+/// Settings of git-signify, read from the system, global, repository
+/// and worktree git config files. Values passed on the command line
+/// always take precedence over these settings.
+pub struct Config {
+    /// The git config, with all of its levels merged.
+    config: git2::Config,
+    /// The directory relative paths in the config are resolved against.
+    base_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// Open the git config of the repository in the current directory,
+    /// or the global git config, outside of a repository. Repositories
+    /// which fail to open, including the one named by `GIT_DIR`, are
+    /// reported as errors.
+    pub fn open() -> Result<Self> {
+        let repo = match Repository::open_from_env() {
+            Ok(repo) => repo,
+            Err(e) if e.code() == ErrorCode::NotFound && env::var_os("GIT_DIR").is_none() => {
+                let config = git2::Config::open_default().context("Failed to open git config")?;
+                return Ok(Self {
+                    config,
+                    base_dir: None,
+                });
+            }
+            Err(e) => return Err(e).context("Failed to open git repository"),
+        };
+
+        let config = repo.config().context("Failed to open git config")?;
+        let base_dir = repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf();
+
+        Ok(Self {
+            config,
+            base_dir: Some(base_dir),
+        })
+    }
+
+    /// Path to the secret keys to sign with, from `signify.secretKey`.
+    pub fn secret_key(&self, secret_key: Option<PathBuf>) -> Result<PathBuf> {
+        match secret_key {
+            Some(secret_key) => Ok(secret_key),
+            None => self
+                .get_path("signify.secretKey")?
+                .context("No secret key provided, pass -k or set signify.secretKey"),
+        }
+    }
+
+    /// Path to the public keys to verify with, from `signify.publicKeys`.
+    pub fn public_keys(&self, public_keys: Option<PathBuf>) -> Result<PathBuf> {
+        match public_keys {
+            Some(public_keys) => Ok(public_keys),
+            None => self
+                .get_path("signify.publicKeys")?
+                .context("No public key provided, pass -k or set signify.publicKeys"),
+        }
+    }
+
+    /// Name of the remote to exchange signify data with, from
+    /// `signify.defaultRemote`. Defaults to `origin`.
+    pub fn default_remote(&self, remote: Option<String>) -> Result<String> {
+        match remote {
+            Some(remote) => Ok(remote),
+            None => Ok(self
+                .get_string("signify.defaultRemote")?
+                .unwrap_or_else(|| "origin".to_owned())),
+        }
+    }
+
+    /// Minimum number of keys whose signatures must verify, from
+    /// `signify.threshold`.
+    pub fn threshold(&self, threshold: Option<usize>) -> Result<Option<usize>> {
+        if threshold.is_some() {
+            return Ok(threshold);
+        }
+        not_found_as_none(self.config.get_i64("signify.threshold"))
+            .context("Failed to read signify.threshold from git config")?
+            .map(|threshold| {
+                usize::try_from(threshold).context("Invalid value of signify.threshold")
+            })
+            .transpose()
+    }
+
+    /// Globs of the references which may only point at signed objects,
+    /// from the multi-valued `signify.protectedRefs`. Defaults to
+    /// `refs/tags/v*`.
+    pub fn protected_refs(&self) -> Result<Vec<String>> {
+        let mut globs = self.get_strings("signify.protectedRefs")?;
+        if globs.is_empty() {
+            globs.push("refs/tags/v*".to_owned());
+        }
+        Ok(globs)
+    }
+
+    /// Types of attestations required over verified objects. Defaults
+    /// to the multi-valued `signify.requiredAttestations`.
+    pub fn required_attestations(&self, attestations: Vec<String>) -> Result<Vec<String>> {
+        if !attestations.is_empty() {
+            return Ok(attestations);
+        }
+        self.get_strings("signify.requiredAttestations")
+    }
+
+    /// Directory git looks for hooks in, from `core.hooksPath`.
+    pub fn hooks_path(&self) -> Result<Option<PathBuf>> {
+        self.get_path("core.hooksPath")
+    }
+
+    /// Read a string from the config.
+    fn get_string(&self, name: &str) -> Result<Option<String>> {
+        not_found_as_none(self.config.get_string(name))
+            .with_context(|| format!("Failed to read {name} from git config"))
+    }
+
+    /// Read every value of a multi-valued string from the config.
+    fn get_strings(&self, name: &str) -> Result<Vec<String>> {
+        let mut values = Vec::new();
+        let mut entries = self
+            .config
+            .multivar(name, None)
+            .with_context(|| format!("Failed to read {name} from git config"))?;
+        while let Some(entry) = entries.next() {
+            let entry = entry.with_context(|| format!("Failed to read {name} from git config"))?;
+            let value = entry
+                .value()
+                .with_context(|| format!("Invalid value of {name}"))?;
+            values.push(value.to_owned());
+        }
+        Ok(values)
+    }
+
+    /// Read a path from the config. Relative paths are resolved against
+    /// the root of the repository, such that they may point to files
+    /// committed to it.
+    fn get_path(&self, name: &str) -> Result<Option<PathBuf>> {
+        let Some(path) = not_found_as_none(self.config.get_path(name))
+            .with_context(|| format!("Failed to read {name} from git config"))?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(match &self.base_dir {
+            Some(base_dir) if path.is_relative() => base_dir.join(path),
+            _ => path,
+        }))
+    }
+}
+
+/// Map config entries that were not found to [`None`].
+fn not_found_as_none<T>(result: Result<T, git2::Error>) -> Result<Option<T>, git2::Error> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,248 @@
+//! Countersign existing signatures, as a witness that saw them.
+//!
+//! A countersignature is a signature over a statement blob, naming the
+//! signer, the signed object and the signature commit, along with the
+//! time at which the witness saw it:
+//!
+//! ```text
+//! git-signify countersignature
+//! signer <fingerprint>
+//! object <signed oid>
+//! signature <signature oid>
+//! time <seconds since the epoch>
+//! ```
+//!
+//! It is stored under `refs/signify/countersignatures/<signer>/<signed
+//! oid>/<witness>`, next to the countersigned signature.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use serde::Serialize;
+
+use crate::output::{self, Format, KeyInfo};
+use crate::raw::sign::sign;
+use crate::utils::{self, PublicKey, TreeSignature};
+
+/// First line of countersignature statements.
+const STATEMENT_HEADER: &str = "git-signify countersignature";
+
+/// Countersignature made by the `countersign` command, as described in
+/// JSON output.
+#[derive(Serialize)]
+struct Countersignature {
+    /// The key of the witness.
+    witness: KeyInfo,
+    /// The reference of the countersigned signature.
+    signature_ref: String,
+    /// The reference the countersignature is stored under.
+    countersignature_ref: String,
+    /// Whether the countersignature was created, or already existed.
+    created: bool,
+}
+
+/// Execute the `countersign` command. Countersigns the signatures over
+/// `rev` with every key at `key_path`, optionally only those made by
+/// `signers`.
+pub fn command(
+    key_path: PathBuf,
+    rev: String,
+    signers: Option<BTreeSet<Oid>>,
+    format: Format,
+) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let object_oid = repo
+        .revparse_single(&rev)
+        .with_context(|| format!("Failed to look-up revision {rev}"))?
+        .id();
+
+    let mut signatures = Vec::new();
+    for maybe_ref in repo
+        .references_glob(&format!(
+            "{}*/{object_oid}",
+            utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX
+        ))
+        .context("Failed to look-up signature refs")?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        let refname = reference.name().context("Invalid reference name")?;
+        let (Some((signer, _)), Some(signature_oid)) = (
+            refname
+                .strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+                .and_then(utils::parse_signer_and_oid),
+            reference.target(),
+        ) else {
+            continue;
+        };
+        if signers
+            .as_ref()
+            .is_none_or(|signers| signers.contains(&signer))
+        {
+            // witnesses only vouch for signatures over the named object
+            TreeSignature::load_oid_over(&repo, signature_oid, object_oid)
+                .with_context(|| format!("Refusing to countersign {refname}"))?;
+            signatures.push((refname.to_owned(), signer, signature_oid));
+        }
+    }
+    anyhow::ensure!(
+        !signatures.is_empty(),
+        "No signatures over {rev} to countersign"
+    );
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System clock is set before the epoch")?
+        .as_secs();
+
+    let mut countersignatures = Vec::new();
+    for (path, secret_key) in utils::get_secret_keys(key_path)? {
+        let public_key = secret_key.public_key()?;
+        let witness = public_key.fingerprint()?;
+        for (signature_ref, signer, signature_oid) in &signatures {
+            let reference = utils::craft_countersignature_reference(*signer, object_oid, witness);
+            // countersignatures of replaced signatures are renewed
+            let countersigned = repo.refname_to_id(&reference).is_ok_and(|existing| {
+                verify_countersignature(
+                    &repo,
+                    &public_key,
+                    existing,
+                    *signer,
+                    object_oid,
+                    *signature_oid,
+                )
+                .is_ok()
+            });
+            let created = !countersigned;
+            if created {
+                let statement = format!(
+                    "{STATEMENT_HEADER}\nsigner {signer}\nobject {object_oid}\n\
+                     signature {signature_oid}\ntime {time}\n"
+                );
+                let statement_oid = repo
+                    .blob(statement.as_bytes())
+                    .context("Failed to write statement to the object store")?;
+                let tree_oid = sign(&repo, &secret_key, &statement_oid.to_string())?;
+                repo.reference(
+                    &reference,
+                    tree_oid,
+                    true,
+                    "git-signify countersign: add countersignature",
+                )
+                .context("Failed to store reference to countersignature")?;
+            }
+            countersignatures.push(Countersignature {
+                witness: KeyInfo::new(path.clone(), &public_key)?,
+                signature_ref: signature_ref.clone(),
+                countersignature_ref: reference,
+                created,
+            });
+        }
+    }
+
+    if format.is_json() {
+        return output::print_json("countersign", &countersignatures);
+    }
+
+    println!("Countersigned signatures:");
+    for countersignature in &countersignatures {
+        println!(
+            "  - {} ({}{})",
+            countersignature.signature_ref,
+            countersignature.witness.key_path.display(),
+            if countersignature.created {
+                ""
+            } else {
+                ", already countersigned"
+            }
+        );
+    }
+    println!("Countersignatures stored under:");
+    for countersignature in &countersignatures {
+        println!("  - {}", countersignature.countersignature_ref);
+    }
+
+    Ok(())
+}
+
+/// Find the `witnesses` whose countersignature over the signature
+/// `signature_oid`, made by `signer` over `object_oid`, verifies.
+pub fn verify_countersignatures(
+    repo: &Repository,
+    witnesses: &BTreeMap<PathBuf, PublicKey>,
+    signer: Oid,
+    object_oid: Oid,
+    signature_oid: Oid,
+) -> Result<Vec<PathBuf>> {
+    let mut verified = Vec::new();
+
+    for (path, public_key) in witnesses {
+        for witness in public_key.equivalent_fingerprints()? {
+            let reference = utils::craft_countersignature_reference(signer, object_oid, witness);
+            let Ok(countersignature_oid) = repo.refname_to_id(&reference) else {
+                continue;
+            };
+            if verify_countersignature(
+                repo,
+                public_key,
+                countersignature_oid,
+                signer,
+                object_oid,
+                signature_oid,
+            )
+            .is_ok()
+            {
+                verified.push(path.clone());
+                break;
+            }
+        }
+    }
+
+    Ok(verified)
+}
+
+/// Verify the countersignature `countersignature_oid` by `public_key`,
+/// and check that its statement names the given signature.
+fn verify_countersignature(
+    repo: &Repository,
+    public_key: &PublicKey,
+    countersignature_oid: Oid,
+    signer: Oid,
+    object_oid: Oid,
+    signature_oid: Oid,
+) -> Result<()> {
+    let tree_sig = TreeSignature::load_oid(repo, countersignature_oid)?;
+    let statement_oid = tree_sig.dereference()?;
+    tree_sig.verify(public_key)?;
+
+    let statement = repo
+        .find_blob(statement_oid)
+        .context("Countersignature statement is not a blob")?;
+    let statement = std::str::from_utf8(statement.content())
+        .context("Countersignature statement is not UTF-8")?;
+    let mut lines = statement.lines();
+    anyhow::ensure!(
+        lines.next() == Some(STATEMENT_HEADER),
+        "Not a countersignature statement"
+    );
+
+    let expected = [
+        ("signer", signer),
+        ("object", object_oid),
+        ("signature", signature_oid),
+    ];
+    for (field, oid) in expected {
+        let value = lines
+            .next()
+            .and_then(|line| line.strip_prefix(field)?.strip_prefix(' '))
+            .with_context(|| format!("Countersignature statement has no {field}"))?;
+        anyhow::ensure!(
+            value == oid.to_string(),
+            "Countersignature statement names {field} {value}, rather than {oid}"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! Select which signify references to exchange with remotes.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use git2::{Oid, Repository, RevparseMode, Sort};
+
+use crate::log;
+use crate::utils;
+
+/// Namespace of signify references.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Namespace {
+    /// Signatures, under `refs/signify/signatures`
+    Signatures,
+    /// Countersignatures by witnesses, under
+    /// `refs/signify/countersignatures`
+    Countersignatures,
+    /// Attestations of typed claims, under `refs/signify/attestations`
+    Attestations,
+    /// Public keys, under `refs/signify/keys`
+    Keys,
+    /// Verification policies, under `refs/signify/policy`
+    Policy,
+    /// The transparency log, `refs/signify/log`, which is only ever
+    /// fast-forwarded when pulled
+    Log,
+}
+
+impl Namespace {
+    /// Git reference prefix of this namespace, or the reference of the
+    /// transparency log.
+    pub const fn prefix(self) -> &'static str {
+        match self {
+            Self::Signatures => utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX,
+            Self::Countersignatures => utils::ALL_SIGNIFY_COUNTERSIGNATURE_REFS_PREFIX,
+            Self::Attestations => utils::ALL_SIGNIFY_ATTESTATION_REFS_PREFIX,
+            Self::Keys => "refs/signify/keys/",
+            Self::Policy => "refs/signify/policy/",
+            Self::Log => log::entry::LOG_REF,
+        }
+    }
+
+    /// Check if the reference `refname` belongs to this namespace.
+    pub fn contains(self, refname: &str) -> bool {
+        match self {
+            Self::Log => refname == log::entry::LOG_REF,
+            _ => refname.starts_with(self.prefix()),
+        }
+    }
+}
+
+/// Command line arguments selecting signify references.
+#[derive(clap::Args)]
+pub struct FilterArgs {
+    /// Only select references in the given namespace, may be
+    /// repeated; defaults to signatures, countersignatures and
+    /// attestations if filtering by key or revision, and to all
+    /// namespaces otherwise
+    #[arg(long = "namespace", value_enum)]
+    namespaces: Vec<Namespace>,
+
+    /// Only select signatures made by the key with the given
+    /// fingerprint, may be repeated
+    #[arg(long = "fingerprint")]
+    fingerprints: Vec<String>,
+
+    /// Only select signatures made by the public keys at the
+    /// given path, may be repeated
+    #[arg(long = "signer")]
+    signers: Vec<PathBuf>,
+
+    /// Only select signatures over the given revision, or over
+    /// the commits in the given range (e.g. `v1.0..v2.0`), may
+    /// be repeated
+    #[arg(short = 'r', long = "rev")]
+    revisions: Vec<String>,
+
+    /// Print the refspecs which would be exchanged with the
+    /// remote, without contacting it
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+}
+
+/// Filter over signify references.
+pub struct RefFilter {
+    /// Selected namespaces. Empty if all namespaces are selected.
+    namespaces: Vec<Namespace>,
+    /// Fingerprints of the selected signers, if any were given.
+    signers: Option<BTreeSet<Oid>>,
+    /// Ids of the selected signed objects, if any were given.
+    objects: Option<BTreeSet<Oid>>,
+}
+
+impl FilterArgs {
+    /// Resolve the keys and revisions given on the command line
+    /// into a [`RefFilter`].
+    pub fn into_filter(self, repo: &Repository) -> Result<RefFilter> {
+        let signers = resolve_signers(&self.fingerprints, self.signers)?;
+        let objects = resolve_revisions(repo, &self.revisions)?;
+
+        let namespaces = if self.namespaces.is_empty() && (signers.is_some() || objects.is_some()) {
+            vec![
+                Namespace::Signatures,
+                Namespace::Countersignatures,
+                Namespace::Attestations,
+            ]
+        } else {
+            self.namespaces
+        };
+
+        Ok(RefFilter {
+            namespaces,
+            signers,
+            objects,
+        })
+    }
+}
+
+impl RefFilter {
+    /// Whether this filter selects every signify reference.
+    pub fn is_empty(&self) -> bool {
+        self.namespaces.is_empty() && self.signers.is_none() && self.objects.is_none()
+    }
+
+    /// Check if the reference `refname` is selected by this filter.
+    /// Key and revision filters only apply to signatures, to the
+    /// countersignatures over them, and to attestations.
+    pub fn matches(&self, refname: &str) -> bool {
+        let Some(&namespace) = Namespace::value_variants()
+            .iter()
+            .find(|namespace| namespace.contains(refname))
+        else {
+            // signatures fetched from remotes, or unknown data
+            return false;
+        };
+
+        if !self.namespaces.is_empty() && !self.namespaces.contains(&namespace) {
+            return false;
+        }
+        let signer_and_oid = match namespace {
+            Namespace::Signatures => &refname[namespace.prefix().len()..],
+            // `<signer>/<oid>/<witness>`
+            Namespace::Countersignatures => {
+                let Some((signer_and_oid, _)) =
+                    refname[namespace.prefix().len()..].rsplit_once('/')
+                else {
+                    return false;
+                };
+                signer_and_oid
+            }
+            // `<type>/<signer>/<oid>`
+            Namespace::Attestations => {
+                let Some((_, signer_and_oid)) = refname[namespace.prefix().len()..].split_once('/')
+                else {
+                    return false;
+                };
+                signer_and_oid
+            }
+            Namespace::Keys | Namespace::Policy | Namespace::Log => return true,
+        };
+
+        let Some((signer, oid)) = utils::parse_signer_and_oid(signer_and_oid) else {
+            return false;
+        };
+
+        self.signers
+            .as_ref()
+            .is_none_or(|signers| signers.contains(&signer))
+            && self
+                .objects
+                .as_ref()
+                .is_none_or(|objects| objects.contains(&oid))
+    }
+}
+
+/// Collect the fingerprints of the given keys, along with the given
+/// fingerprints, or return [`None`] if no keys were given at all.
+pub fn resolve_signers(
+    fingerprints: &[String],
+    key_paths: Vec<PathBuf>,
+) -> Result<Option<BTreeSet<Oid>>> {
+    if fingerprints.is_empty() && key_paths.is_empty() {
+        return Ok(None);
+    }
+
+    let mut signers = BTreeSet::new();
+    for fingerprint in fingerprints {
+        signers.insert(
+            Oid::from_str(fingerprint)
+                .with_context(|| format!("Invalid key fingerprint {fingerprint}"))?,
+        );
+    }
+    for path in key_paths {
+        for public_key in utils::get_public_keys(path)?.into_values() {
+            signers.extend(public_key.equivalent_fingerprints()?);
+        }
+    }
+
+    Ok(Some(signers))
+}
+
+/// Collect the ids of the objects named by the given revisions, or
+/// return [`None`] if no revisions were given at all.
+pub fn resolve_revisions(repo: &Repository, revisions: &[String]) -> Result<Option<BTreeSet<Oid>>> {
+    if revisions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut objects = BTreeSet::new();
+    for rev in revisions {
+        resolve_revision(repo, rev, &mut objects)?;
+    }
+
+    Ok(Some(objects))
+}
+
+/// Collect the ids of the objects named by `rev`. Ranges yield all
+/// the commits they contain, while single revisions yield both the
+/// named object and the commit it peels to, such that signatures
+/// over annotated tags and over the tagged commits are selected.
+fn resolve_revision(repo: &Repository, rev: &str, objects: &mut BTreeSet<Oid>) -> Result<()> {
+    let spec = repo
+        .revparse(rev)
+        .with_context(|| format!("Failed to look-up revision {rev}"))?;
+
+    if spec.mode().contains(RevparseMode::RANGE) {
+        let mut walk = repo.revwalk().context("Failed to walk revisions")?;
+        walk.set_sorting(Sort::NONE)
+            .context("Failed to walk revisions")?;
+        walk.push_range(rev)
+            .with_context(|| format!("Invalid revision range {rev}"))?;
+        for maybe_oid in walk {
+            objects.insert(maybe_oid.context("Failed to walk revisions")?);
+        }
+        return Ok(());
+    }
+
+    let object = spec
+        .from()
+        .with_context(|| format!("Failed to look-up revision {rev}"))?;
+    objects.insert(object.id());
+    if let Ok(commit) = object.peel_to_commit() {
+        objects.insert(commit.id());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_namespace_is_exact() {
+        assert!(Namespace::Log.contains(log::entry::LOG_REF));
+        assert!(!Namespace::Log.contains(&format!("{}s/other", log::entry::LOG_REF)));
+        assert!(Namespace::Keys.contains("refs/signify/keys/release"));
+    }
+}
@@ -4,14 +4,23 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 
+use super::output::{self, Format, KeyInfo};
 use super::utils;
 
 /// Execute the `fingerprint` command.
-pub fn command(key_path: PathBuf) -> Result<()> {
+pub fn command(key_path: PathBuf, format: Format) -> Result<()> {
+    let mut keys = Vec::new();
     for (path, public_key) in utils::get_public_keys(key_path)? {
+        if format.is_json() {
+            keys.push(KeyInfo::new(path, &public_key)?);
+            continue;
+        }
         let hash = public_key.fingerprint()?;
         println!("{}:", path.display());
         println!("  - {hash}");
     }
+    if format.is_json() {
+        output::print_json("fingerprint", &keys)?;
+    }
     Ok(())
 }
@@ -0,0 +1,71 @@
+//! Audit the signatures stored in this repository.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::utils::{self, Keyring, SignatureStatus};
+
+/// Execute the `fsck` command. Every signature must sign over the
+/// object named by its reference, and verify under the key whose
+/// fingerprint is named by its reference.
+pub fn command(key_path: PathBuf) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let keyring = Keyring::open(key_path)?;
+
+    let mut checked = 0;
+    let mut unknown = Vec::new();
+    let mut invalid = Vec::new();
+
+    for maybe_ref in repo
+        .references_glob(utils::ALL_SIGNIFY_SIGNATURE_REFS)
+        .context("Failed to look-up signature refs")?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        let refname = reference
+            .name()
+            .context("Invalid reference name")?
+            .to_owned();
+        checked += 1;
+
+        let Some((signer, signed_oid)) = refname
+            .strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+            .and_then(utils::parse_signer_and_oid)
+        else {
+            invalid.push(format!("{refname} (malformed reference name)"));
+            continue;
+        };
+        let Some(signature_oid) = reference.target() else {
+            invalid.push(format!("{refname} (not a direct reference)"));
+            continue;
+        };
+
+        match utils::check_signature(&repo, &keyring, signer, signed_oid, signature_oid) {
+            SignatureStatus::Valid { .. } => {}
+            SignatureStatus::UnknownKey => unknown.push(refname),
+            SignatureStatus::Invalid(e) => invalid.push(format!("{refname} ({e:#})")),
+        }
+    }
+
+    if !unknown.is_empty() {
+        println!("Signatures by unknown keys:");
+        for refname in &unknown {
+            println!("  - {refname}");
+        }
+    }
+    if !invalid.is_empty() {
+        println!("Invalid signatures:");
+        for refname in &invalid {
+            println!("  - {refname}");
+        }
+    }
+
+    anyhow::ensure!(
+        invalid.is_empty(),
+        "Found {} invalid signatures out of {checked}",
+        invalid.len()
+    );
+
+    println!("Checked {checked} signatures");
+    Ok(())
+}
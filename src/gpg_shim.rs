@@ -0,0 +1,259 @@
+//! Emulate the subset of the `gpg` command line used by git, such
+//! that `git-signify` keys can be configured as `gpg.program`.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+
+use crate::config::Config;
+use crate::utils;
+
+/// First line of the armor wrapping signatures, as expected by git.
+const ARMOR_BEGIN: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// Last line of the armor wrapping signatures, as expected by git.
+const ARMOR_END: &str = "-----END PGP SIGNATURE-----";
+
+/// Operation requested by git.
+enum Mode {
+    /// Create a detached signature with the given key.
+    Sign { key: String },
+    /// Verify the detached signature stored in the given file.
+    Verify { signature_file: PathBuf },
+}
+
+/// Arguments passed by git to `gpg.program`.
+struct GpgArgs {
+    /// File descriptor to write machine readable status lines to.
+    status_fd: Option<u32>,
+    /// The requested operation.
+    mode: Mode,
+}
+
+/// Execute the `gpg-shim` command.
+pub fn command(config: &Config, args: Vec<String>) -> Result<()> {
+    let args = parse_args(args)?;
+
+    let mut payload = Vec::new();
+    io::stdin()
+        .read_to_end(&mut payload)
+        .context("Failed to read payload from standard input")?;
+
+    let mut status = StatusWriter::new(args.status_fd)?;
+
+    match args.mode {
+        Mode::Sign { key } => sign(config, &mut status, &key, &payload),
+        Mode::Verify { signature_file } => verify(config, &mut status, &signature_file, &payload),
+    }
+}
+
+fn sign(config: &Config, status: &mut StatusWriter, key: &str, payload: &[u8]) -> Result<()> {
+    let secret_key = {
+        let mut keys = utils::get_secret_keys(signing_key_path(config, key)?)?.into_values();
+        let secret_key = keys.next().context("No secret key found to sign with")?;
+        anyhow::ensure!(
+            keys.next().is_none(),
+            "Multiple secret keys found, but git can only sign with one"
+        );
+        secret_key
+    };
+    let fingerprint = secret_key.public_key()?.fingerprint()?;
+
+    status.line(format_args!("KEY_CONSIDERED {fingerprint} 0"))?;
+    status.line(format_args!("BEGIN_SIGNING H8"))?;
+
+    let signature = secret_key.sign(payload)?;
+    let signature = std::str::from_utf8(&signature).context("Found non-utf8 data in signature")?;
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{ARMOR_BEGIN}")
+        .and_then(|()| write!(stdout, "{}", signature.trim_end()))
+        .and_then(|()| writeln!(stdout))
+        .and_then(|()| writeln!(stdout, "{ARMOR_END}"))
+        .and_then(|()| stdout.flush())
+        .context("Failed to write signature to standard output")?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System clock is set before the unix epoch")?
+        .as_secs();
+    status.line(format_args!(
+        "SIG_CREATED D 22 8 00 {timestamp} {fingerprint}"
+    ))?;
+
+    Ok(())
+}
+
+fn verify(
+    config: &Config,
+    status: &mut StatusWriter,
+    signature_file: &Path,
+    payload: &[u8],
+) -> Result<()> {
+    let armored_signature =
+        fs::read_to_string(signature_file).context("Failed to read signature file")?;
+    let signature = strip_armor(&armored_signature)?;
+
+    let public_keys_path = config.public_keys(env::var_os("GIT_KEY_PUB").map(PathBuf::from))?;
+
+    status.line(format_args!("NEWSIG"))?;
+
+    for (path, public_key) in utils::get_public_keys(public_keys_path)? {
+        if public_key.verify(payload, signature.as_bytes()).is_err() {
+            continue;
+        }
+
+        let fingerprint = public_key.fingerprint()?.to_string();
+        let key_id = &fingerprint[fingerprint.len() - 16..];
+        let name = path.display();
+
+        eprintln!("git-signify: Good signature from {name}");
+        eprintln!("git-signify: Key fingerprint {fingerprint}");
+
+        status.line(format_args!("GOODSIG {key_id} {name}"))?;
+        status.line(format_args!(
+            "VALIDSIG {fingerprint} 1970-01-01 0 0 4 0 22 8 00 {fingerprint}"
+        ))?;
+        status.line(format_args!("TRUST_FULLY 0 pgp"))?;
+
+        return Ok(());
+    }
+
+    eprintln!("git-signify: No public key could verify the signature");
+    status.line(format_args!("ERRSIG 0000000000000000 22 8 00 0 9"))?;
+    status.line(format_args!("NO_PUBKEY 0000000000000000"))?;
+
+    anyhow::bail!("Failed to verify signature")
+}
+
+/// Resolve the path of the secret key identified by `key`, which is the
+/// value of `user.signingKey`. Falls back to `GIT_KEY_SEC` and then to
+/// `signify.secretKey`, in case git passed the committer identity instead.
+fn signing_key_path(config: &Config, key: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(key);
+    if path.exists() {
+        return Ok(path);
+    }
+    config.secret_key(env::var_os("GIT_KEY_SEC").map(PathBuf::from))
+}
+
+/// Remove the armor surrounding a signature produced by [`sign`].
+fn strip_armor(armored_signature: &str) -> Result<String> {
+    let (_, rest) = armored_signature
+        .split_once(ARMOR_BEGIN)
+        .context("No signature armor header found")?;
+    let (signature, _) = rest
+        .split_once(ARMOR_END)
+        .context("No signature armor footer found")?;
+    Ok(format!("{}\n", signature.trim()))
+}
+
+/// Parse the arguments git passes to `gpg.program`.
+fn parse_args(args: Vec<String>) -> Result<GpgArgs> {
+    let mut status_fd = None;
+    let mut sign = false;
+    let mut local_user = None;
+    let mut signature_file = None;
+    let mut verify = false;
+
+    let mut args_iter = args.into_iter();
+
+    while let Some(arg) = args_iter.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_owned(), Some(value.into())),
+            _ => (arg, None),
+        };
+        let mut value = |name: &str| {
+            inline_value
+                .clone()
+                .or_else(|| args_iter.next())
+                .with_context(|| format!("Missing value of {name}"))
+        };
+
+        match flag.as_str() {
+            "--status-fd" => {
+                status_fd = Some(
+                    value("--status-fd")?
+                        .parse()
+                        .context("Invalid status file descriptor")?,
+                );
+            }
+            "--keyid-format" => {
+                value("--keyid-format")?;
+            }
+            "-b" | "--detach-sign" | "-s" | "--sign" | "-a" | "--armor" => sign = true,
+            "-u" | "--local-user" => {
+                sign = true;
+                local_user = Some(value("--local-user")?);
+            }
+            "--verify" => verify = true,
+            "-" => {}
+            short if short.starts_with('-') && !short.starts_with("--") => {
+                // clusters of short options, such as `-bsau <key>`
+                let mut chars = short[1..].chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        'b' | 's' | 'a' => sign = true,
+                        'u' => {
+                            sign = true;
+                            let rest: String = chars.by_ref().collect();
+                            local_user = Some(if rest.is_empty() { value("-u")? } else { rest });
+                        }
+                        c => anyhow::bail!("Unsupported gpg option -{c}"),
+                    }
+                }
+            }
+            flag if flag.starts_with("--") => {
+                anyhow::bail!("Unsupported gpg option {flag}");
+            }
+            file if verify && signature_file.is_none() => {
+                signature_file = Some(PathBuf::from(file));
+            }
+            other => anyhow::bail!("Unexpected gpg argument {other:?}"),
+        }
+    }
+
+    let mode = match (sign, verify) {
+        (true, false) => Mode::Sign {
+            key: local_user.context("No signing key provided with -u")?,
+        },
+        (false, true) => Mode::Verify {
+            signature_file: signature_file.context("No signature file provided to --verify")?,
+        },
+        _ => anyhow::bail!("Expected either a signing or a verification request"),
+    };
+
+    Ok(GpgArgs { status_fd, mode })
+}
+
+/// Writer of gpg status lines.
+struct StatusWriter {
+    inner: Option<Box<dyn Write>>,
+}
+
+impl StatusWriter {
+    /// Create a new [`StatusWriter`] over the given file descriptor.
+    fn new(fd: Option<u32>) -> Result<Self> {
+        let inner: Option<Box<dyn Write>> = match fd {
+            None => None,
+            Some(1) => Some(Box::new(io::stdout())),
+            Some(2) => Some(Box::new(io::stderr())),
+            Some(fd) => anyhow::bail!("Unsupported status file descriptor {fd}"),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Write a status line with the given contents.
+    fn line(&mut self, contents: std::fmt::Arguments<'_>) -> Result<()> {
+        let Some(inner) = self.inner.as_mut() else {
+            return Ok(());
+        };
+        writeln!(inner, "[GNUPG:] {contents}")
+            .and_then(|()| inner.flush())
+            .context("Failed to write gpg status line")
+    }
+}
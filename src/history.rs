@@ -0,0 +1,222 @@
+//! Record and show the history of signature references.
+//!
+//! Each change to a signature reference is recorded as an entry in the
+//! reflog of [`HISTORY_REF`], which points at the last signature that
+//! was added, replaced or removed. This keeps removed and replaced
+//! signatures reachable, along with who changed them, and when.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository, Time};
+use serde::Serialize;
+
+use crate::log;
+use crate::output::{self, Format};
+use crate::utils;
+
+/// Git reference whose reflog records the history of signatures.
+/// It is never exchanged with remotes.
+pub const HISTORY_REF: &str = "refs/signify/history";
+
+/// Change made to a signature reference.
+#[derive(Copy, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// A signature was added.
+    Added,
+    /// A signature was replaced by another one.
+    Replaced,
+    /// A signature was removed.
+    Removed,
+}
+
+impl Event {
+    /// Name of the event, as recorded in the history.
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Replaced => "replaced",
+            Self::Removed => "removed",
+        }
+    }
+
+    /// Parse the name of an event recorded in the history.
+    fn from_str(event: &str) -> Option<Self> {
+        match event {
+            "added" => Some(Self::Added),
+            "replaced" => Some(Self::Replaced),
+            "removed" => Some(Self::Removed),
+            _ => None,
+        }
+    }
+}
+
+/// Entry of the history of a signature, as described in JSON output.
+#[derive(Serialize)]
+struct Entry {
+    /// Seconds since the epoch at which the change was made.
+    time: i64,
+    /// Offset of the time zone of the change, in minutes.
+    offset_minutes: i32,
+    /// Name of who made the change.
+    name: String,
+    /// Email of who made the change.
+    email: String,
+    /// The `git-signify` command that made the change.
+    command: String,
+    /// The change that was made.
+    event: Event,
+    /// The signature reference that was changed.
+    signature_ref: String,
+    /// The signature which was added, the replacement of a signature,
+    /// or the removed signature.
+    signature_oid: String,
+}
+
+/// Record that `command` made the change `event` to the signature
+/// reference `refname`. `signature_oid` is the added signature, the
+/// replacement of a signature, or the removed signature. New signatures
+/// are also appended to the transparency log, if it is enabled.
+pub fn record(
+    repo: &Repository,
+    command: &str,
+    event: Event,
+    refname: &str,
+    signature_oid: Oid,
+) -> Result<()> {
+    // entries point at signatures no longer reachable from the history
+    // ref, which `git gc` would otherwise expire after 30 days
+    utils::keep_reflog(repo, HISTORY_REF)?;
+    repo.reference(
+        HISTORY_REF,
+        signature_oid,
+        true,
+        &format!("git-signify {command}: {} {refname}", event.as_str()),
+    )
+    .context("Failed to record the signature history")?;
+
+    if !matches!(event, Event::Removed) && log::entry::enabled(repo)? {
+        if let Some(entry) = log::entry::Entry::from_ref(refname, signature_oid) {
+            log::entry::append(repo, &[entry])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Execute the `history` command, showing when the signatures over
+/// `rev`, or over the commit it peels to, were added, replaced or
+/// removed, and by whom.
+pub fn command(rev: String, format: Format) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let object = repo
+        .revparse_single(&rev)
+        .with_context(|| format!("Failed to look-up revision {rev}"))?;
+    let mut oids = BTreeSet::from([object.id()]);
+    if let Ok(commit) = object.peel_to_commit() {
+        oids.insert(commit.id());
+    }
+
+    let reflog = repo
+        .reflog(HISTORY_REF)
+        .context("Failed to read the signature history")?;
+
+    let mut entries = Vec::new();
+    // reflogs are stored from the newest entry to the oldest one
+    for reflog_entry in reflog.iter().rev() {
+        let Some((command, event, refname)) = reflog_entry.message().and_then(parse_message) else {
+            continue;
+        };
+        let Some((_, signed_oid)) = refname
+            .strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+            .and_then(utils::parse_signer_and_oid)
+        else {
+            continue;
+        };
+        if !oids.contains(&signed_oid) {
+            continue;
+        }
+
+        let committer = reflog_entry.committer();
+        let when = committer.when();
+        entries.push(Entry {
+            time: when.seconds(),
+            offset_minutes: when.offset_minutes(),
+            name: committer.name().unwrap_or_default().to_owned(),
+            email: committer.email().unwrap_or_default().to_owned(),
+            command: command.to_owned(),
+            event,
+            signature_ref: refname.to_owned(),
+            signature_oid: reflog_entry.id_new().to_string(),
+        });
+    }
+
+    if format.is_json() {
+        return output::print_json("history", &entries);
+    }
+
+    if entries.is_empty() {
+        println!("No recorded history of signatures over {rev}");
+        return Ok(());
+    }
+
+    println!("History of signatures over {rev}:");
+    for entry in &entries {
+        println!(
+            "  - {} {} by {} <{}> ({}): {} ({})",
+            DisplayTime(Time::new(entry.time, entry.offset_minutes)),
+            entry.event.as_str(),
+            entry.name,
+            entry.email,
+            entry.command,
+            entry.signature_ref,
+            entry.signature_oid
+        );
+    }
+
+    Ok(())
+}
+
+/// Parse the `git-signify <command>: <event> <refname>` messages
+/// recorded by [`record`].
+fn parse_message(message: &str) -> Option<(&str, Event, &str)> {
+    let (command, change) = message.strip_prefix("git-signify ")?.split_once(": ")?;
+    let (event, refname) = change.split_once(' ')?;
+    Some((command, Event::from_str(event)?, refname))
+}
+
+/// Display a [`Time`] as `YYYY-MM-DD HH:MM:SS +ZZZZ`, in its own
+/// time zone.
+struct DisplayTime(Time);
+
+impl fmt::Display for DisplayTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = i64::from(self.0.offset_minutes());
+        let local = self.0.seconds() + offset * 60;
+        let (days, secs) = (local.div_euclid(86400), local.rem_euclid(86400));
+
+        // civil date from days since the epoch, after Howard Hinnant
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} {}{:02}{:02}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 60,
+            offset.abs() % 60
+        )
+    }
+}
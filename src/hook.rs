@@ -0,0 +1,5 @@
+//! Git hooks enforcing and propagating signatures.
+
+pub mod pre_push;
+pub mod pre_receive;
+pub mod verify;
@@ -0,0 +1,176 @@
+//! Push the signatures over the objects pushed to a remote along
+//! with them.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead};
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+
+use crate::remote;
+use crate::utils;
+
+/// Update of a remote reference, as read by the `pre-push` hook.
+struct RefUpdate {
+    /// The name of the local reference being pushed.
+    local_ref: String,
+    /// The object being pushed, zero if the remote ref is deleted.
+    local_oid: Oid,
+    /// The object the remote ref currently points at, if any.
+    remote_oid: Oid,
+}
+
+/// Execute the `hook pre-push` command. The signature refs over the
+/// objects pushed to `remote` are pushed to it as well. If
+/// `block_unsigned` is set, pushes of refs matching `protected_refs`
+/// to objects without any signature are blocked.
+pub fn command(
+    remote: String,
+    url: Option<String>,
+    protected_refs: Vec<String>,
+    block_unsigned: bool,
+) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let updates = read_updates()?;
+    let signatures = index_signatures(&repo)?;
+
+    let mut refspecs = BTreeSet::new();
+    let mut unsigned = Vec::new();
+
+    for update in &updates {
+        if update.local_oid.is_zero() {
+            continue;
+        }
+
+        let mut signed = false;
+        for oid in pushed_objects(&repo, update)? {
+            if let Some(refnames) = signatures.get(&oid) {
+                signed |= oid == update.local_oid || peels_to(&repo, update.local_oid, oid);
+                refspecs.extend(
+                    refnames
+                        .iter()
+                        .map(|refname| format!("{refname}:{refname}")),
+                );
+            }
+        }
+
+        if block_unsigned
+            && !signed
+            && protected_refs
+                .iter()
+                .any(|glob| utils::glob_matches(glob, &update.local_ref))
+        {
+            unsigned.push(update.local_ref.clone());
+        }
+    }
+
+    if !unsigned.is_empty() {
+        eprintln!("Refusing to push unsigned protected refs:");
+        for refname in &unsigned {
+            eprintln!("  - {refname}");
+        }
+        eprintln!("Sign them with `git signify sign`, or push with --no-verify");
+        anyhow::bail!("Blocked push of unsigned protected refs");
+    }
+
+    if refspecs.is_empty() {
+        return Ok(());
+    }
+
+    // the remote may not be named, if git was given a url to push to
+    let remote = if repo.find_remote(&remote).is_ok() {
+        remote
+    } else {
+        url.unwrap_or(remote)
+    };
+    let refspecs: Vec<_> = refspecs.into_iter().collect();
+
+    eprintln!("Pushing {} signatures to {remote}", refspecs.len());
+    remote::push(&repo, &remote, &refspecs)
+}
+
+/// Index the local signature refs by the objects they sign.
+fn index_signatures(repo: &Repository) -> Result<HashMap<Oid, Vec<String>>> {
+    let mut signatures: HashMap<_, Vec<_>> = HashMap::new();
+
+    for maybe_ref in repo
+        .references_glob(utils::ALL_SIGNIFY_SIGNATURE_REFS)
+        .context("Failed to look-up signature refs")?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        let refname = reference.name().context("Invalid reference name")?;
+        let Some((_, oid)) = refname
+            .strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+            .and_then(utils::parse_signer_and_oid)
+        else {
+            continue;
+        };
+        // reference names are not authenticated, so make sure
+        // the signature really is over the object they name
+        let Some(signature_oid) = reference.target() else {
+            continue;
+        };
+        if utils::TreeSignature::load_oid_over(repo, signature_oid, oid).is_err() {
+            continue;
+        }
+        signatures.entry(oid).or_default().push(refname.to_owned());
+    }
+
+    Ok(signatures)
+}
+
+/// Collect the objects sent to the remote by `update`: the pushed
+/// object, the commit it peels to, and the commits missing from
+/// the remote ref.
+fn pushed_objects(repo: &Repository, update: &RefUpdate) -> Result<Vec<Oid>> {
+    let mut objects = vec![update.local_oid];
+
+    let Ok(commit) = repo
+        .find_object(update.local_oid, None)
+        .and_then(|object| object.peel_to_commit())
+    else {
+        return Ok(objects);
+    };
+
+    let mut walk = repo.revwalk().context("Failed to walk revisions")?;
+    walk.push(commit.id()).context("Failed to walk revisions")?;
+    if !update.remote_oid.is_zero() && repo.find_object(update.remote_oid, None).is_ok() {
+        walk.hide(update.remote_oid)
+            .context("Failed to walk revisions")?;
+    }
+    for maybe_oid in walk {
+        objects.push(maybe_oid.context("Failed to walk revisions")?);
+    }
+
+    Ok(objects)
+}
+
+/// Check if `oid` peels to the commit `commit_oid`.
+fn peels_to(repo: &Repository, oid: Oid, commit_oid: Oid) -> bool {
+    repo.find_object(oid, None)
+        .and_then(|object| object.peel_to_commit())
+        .is_ok_and(|commit| commit.id() == commit_oid)
+}
+
+/// Read the `<local ref> <local oid> <remote ref> <remote oid>`
+/// lines git feeds to the `pre-push` hook on stdin.
+fn read_updates() -> Result<Vec<RefUpdate>> {
+    let mut updates = Vec::new();
+
+    for line in io::stdin().lock().lines() {
+        let line = line.context("Failed to read ref updates from standard input")?;
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let &[local_ref, local_oid, _remote_ref, remote_oid] = &fields[..] else {
+            anyhow::bail!("Malformed ref update {line:?}");
+        };
+        let parse_oid =
+            |oid: &str| Oid::from_str(oid).with_context(|| format!("Invalid object id {oid}"));
+        updates.push(RefUpdate {
+            local_ref: local_ref.to_owned(),
+            local_oid: parse_oid(local_oid)?,
+            remote_oid: parse_oid(remote_oid)?,
+        });
+    }
+
+    Ok(updates)
+}
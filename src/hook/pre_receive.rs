@@ -0,0 +1,225 @@
+//! Reject pushes of protected references to unsigned objects.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+
+use crate::attest;
+use crate::utils::{self, PublicKey};
+
+/// Update of a reference, as read by the `pre-receive` hook.
+struct RefUpdate {
+    /// The new object id of the reference, zero if it is deleted.
+    new: Oid,
+    /// The name of the reference.
+    refname: String,
+}
+
+/// Execute the `hook pre-receive` command. Each update of a reference
+/// matching one of `protected_refs` must point at an object signed by
+/// at least `threshold` keys of the keyring at `key_path`, and attested
+/// by one of them for each of the `attestations` types. Signatures and
+/// attestations may be pushed along with the protected references.
+pub fn command(
+    key_path: PathBuf,
+    protected_refs: Vec<String>,
+    threshold: Option<usize>,
+    attestations: Vec<String>,
+) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let keys = utils::get_public_keys(key_path)?;
+    let threshold = threshold.unwrap_or(1);
+
+    let updates = read_updates()?;
+
+    // signatures and attestations pushed along with the protected refs,
+    // which have yet to be stored in the repository, or deleted from it
+    let pushed_refs: HashMap<&str, Option<Oid>> = updates
+        .iter()
+        .filter(|update| {
+            update
+                .refname
+                .starts_with(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+                || update
+                    .refname
+                    .starts_with(utils::ALL_SIGNIFY_ATTESTATION_REFS_PREFIX)
+        })
+        .map(|update| {
+            let new = (!update.new.is_zero()).then_some(update.new);
+            (update.refname.as_str(), new)
+        })
+        .collect();
+    let lookup = |refname: &str| match pushed_refs.get(refname) {
+        Some(&new) => new,
+        None => repo.refname_to_id(refname).ok(),
+    };
+
+    let mut rejected = Vec::new();
+
+    for update in &updates {
+        if update.new.is_zero()
+            || !protected_refs
+                .iter()
+                .any(|glob| utils::glob_matches(glob, &update.refname))
+        {
+            continue;
+        }
+
+        let verified_keys = count_signers(&repo, &keys, update.new, lookup)?;
+        if verified_keys < threshold {
+            rejected.push(format!(
+                "{} ({verified_keys} out of the required {threshold} keys signed {})",
+                update.refname, update.new
+            ));
+        }
+
+        for kind in &attestations {
+            if !is_attested(&repo, &keys, kind, update.new, lookup)? {
+                rejected.push(format!(
+                    "{} (no {kind} attestation over {})",
+                    update.refname, update.new
+                ));
+            }
+        }
+    }
+
+    if rejected.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("Protected refs must point at signed objects, rejected:");
+    for refname in &rejected {
+        eprintln!("  - {refname}");
+    }
+    eprintln!(
+        "Sign or attest them with `git signify sign` or `git signify attest`, \
+         and push the signatures or attestations along with them"
+    );
+
+    anyhow::bail!("Rejected push of unsigned protected refs")
+}
+
+/// Count the distinct keys of `keys` which signed `oid`, or the commit
+/// it peels to, such that a key stored in both the signify and minisign
+/// formats counts once. `lookup` resolves signature references.
+fn count_signers(
+    repo: &Repository,
+    keys: &BTreeMap<PathBuf, PublicKey>,
+    oid: Oid,
+    lookup: impl Fn(&str) -> Option<Oid>,
+) -> Result<usize> {
+    let mut signers = HashSet::new();
+    for public_key in keys.values() {
+        if is_signed(repo, public_key, oid, &lookup)? {
+            signers.insert(public_key.identity()?);
+        }
+    }
+    Ok(signers.len())
+}
+
+/// Check if `public_key` signed `oid`, or the commit it peels to.
+/// `lookup` resolves signature references.
+fn is_signed(
+    repo: &Repository,
+    public_key: &PublicKey,
+    oid: Oid,
+    lookup: impl Fn(&str) -> Option<Oid>,
+) -> Result<bool> {
+    for signed_oid in peeled_oids(repo, oid) {
+        for key_fingerprint in public_key.equivalent_fingerprints()? {
+            let refname = utils::craft_signature_reference(key_fingerprint, signed_oid);
+            let Some(signature_oid) = lookup(&refname) else {
+                continue;
+            };
+            if utils::verify_signature_over(repo, public_key, signature_oid, signed_oid).is_ok() {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// Collect `oid`, along with the commit it peels to, if any.
+fn peeled_oids(repo: &Repository, oid: Oid) -> Vec<Oid> {
+    let mut oids = vec![oid];
+    if let Ok(commit) = repo
+        .find_object(oid, None)
+        .and_then(|object| object.peel_to_commit())
+    {
+        if commit.id() != oid {
+            oids.push(commit.id());
+        }
+    }
+    oids
+}
+
+/// Check if some key of `keys` attested the claim `kind` over `oid`, or
+/// over the commit it peels to.
+fn is_attested(
+    repo: &Repository,
+    keys: &BTreeMap<PathBuf, PublicKey>,
+    kind: &str,
+    oid: Oid,
+    lookup: impl Fn(&str) -> Option<Oid> + Copy,
+) -> Result<bool> {
+    for attested_oid in peeled_oids(repo, oid) {
+        if !attest::attested_by(repo, keys, kind, attested_oid, lookup)?.is_empty() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Read the `<old> <new> <ref>` lines git feeds to hooks on stdin.
+fn read_updates() -> Result<Vec<RefUpdate>> {
+    let mut updates = Vec::new();
+
+    for line in io::stdin().lock().lines() {
+        let line = line.context("Failed to read ref updates from standard input")?;
+        let mut fields = line.split_whitespace();
+        let (Some(_old), Some(new), Some(refname)) = (fields.next(), fields.next(), fields.next())
+        else {
+            anyhow::bail!("Malformed ref update {line:?}");
+        };
+        updates.push(RefUpdate {
+            new: Oid::from_str(new).with_context(|| format!("Invalid object id {new}"))?,
+            refname: refname.to_owned(),
+        });
+    }
+
+    Ok(updates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw;
+    use crate::utils::tests::{minisign_fixture, signify_fixture, test_commit, test_repository};
+
+    #[test]
+    fn equivalent_keys_count_once() {
+        let repo = test_repository("equivalent-keys");
+        let commit = test_commit(&repo, "signed", &[]);
+
+        // the same Ed25519 key signs in both formats, under two fingerprints
+        let mut keys = BTreeMap::new();
+        for (path, secret_key) in [
+            ("release.pub", signify_fixture()),
+            ("release.minisign.pub", minisign_fixture()),
+        ] {
+            let public_key = secret_key.public_key().unwrap();
+            let signature = raw::sign::sign(&repo, &secret_key, &commit.to_string()).unwrap();
+            let refname =
+                utils::craft_signature_reference(public_key.fingerprint().unwrap(), commit);
+            repo.reference(&refname, signature, false, "test").unwrap();
+            keys.insert(PathBuf::from(path), public_key);
+        }
+
+        let lookup = |refname: &str| repo.refname_to_id(refname).ok();
+        assert_eq!(count_signers(&repo, &keys, commit, lookup).unwrap(), 1);
+    }
+}
@@ -0,0 +1,327 @@
+//! Verify checked-out or fetched revisions from client-side hooks.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+
+use crate::attest;
+use crate::output::VerificationStatus;
+use crate::utils::{self, PublicKey};
+
+/// Revision checked by the `hook verify` command.
+struct Target {
+    /// How the revision was named, e.g. `HEAD` or a tag.
+    name: String,
+    /// The object the revision points at.
+    oid: Oid,
+}
+
+/// Execute the `hook verify` command. Verifies `HEAD`, or the tags
+/// listed in `FETCH_HEAD` if `fetched` is set, against the keyring at
+/// `key_path`, requiring `attestations` of the given types. Problems
+/// are reported as warnings, or as an error if `fail` is set, such that
+/// the calling hook fails.
+pub fn command(
+    key_path: PathBuf,
+    threshold: Option<usize>,
+    attestations: Vec<String>,
+    fetched: bool,
+    fail: bool,
+) -> Result<()> {
+    let repo = utils::open_repository()?;
+    check_trusted_keyring(&repo, &key_path)?;
+    let policy = Policy {
+        keys: utils::get_public_keys(key_path)?,
+        threshold: threshold.unwrap_or(1),
+        attestations,
+    };
+
+    let problems = if fetched {
+        let mut problems = Vec::new();
+        for target in fetched_tags(&repo)? {
+            problems.extend(check_target(&repo, &policy, &target)?);
+        }
+        problems
+    } else {
+        check_head(&repo, &policy)?
+    };
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("Failed to verify signatures:");
+    for problem in &problems {
+        eprintln!("  - {problem}");
+    }
+    eprintln!("Fetch signatures with `git signify pull`, or check who made these changes");
+
+    if fail {
+        anyhow::bail!("Found unverified revisions");
+    }
+    eprintln!("warning: continuing with unverified revisions");
+
+    Ok(())
+}
+
+/// Check the signatures over `HEAD`, or over the tips merged in if it
+/// is a merge created locally, and describe what fails to verify.
+fn check_head(repo: &Repository, policy: &Policy) -> Result<Vec<String>> {
+    let head = repo.head().context("Failed to look-up HEAD")?;
+    let head = Target {
+        name: "HEAD".to_owned(),
+        oid: head.target().context("HEAD does not point at an object")?,
+    };
+    let mut problems = check_target(repo, policy, &head)?;
+
+    // merge commits made locally by `git merge` are never signed,
+    // unlike the tips which were merged in
+    if !problems.is_empty() {
+        if let Some(parents) = local_merge_parents(repo, head.oid)? {
+            problems.clear();
+            for parent in parents {
+                problems.extend(check_target(repo, policy, &parent)?);
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Collect the merged-in parents of `oid` if it is a merge commit
+/// created in this repository, as recorded by the last `HEAD` reflog
+/// entry moving from its first parent to it. Merges fetched from
+/// elsewhere, including fast-forwards to them, must be signed
+/// themselves.
+fn local_merge_parents(repo: &Repository, oid: Oid) -> Result<Option<Vec<Target>>> {
+    let Ok(merge) = repo.find_commit(oid) else {
+        return Ok(None);
+    };
+    if merge.parent_count() < 2 {
+        return Ok(None);
+    }
+
+    let reflog = repo
+        .reflog("HEAD")
+        .context("Failed to read the HEAD reflog")?;
+    let Some(entry) = reflog.get(0) else {
+        return Ok(None);
+    };
+    let message = entry.message().unwrap_or_default();
+    let created = message.contains(": Merge made by ") || message.starts_with("commit (merge):");
+    if !created || entry.id_new() != oid || entry.id_old() != merge.parent_id(0)? {
+        return Ok(None);
+    }
+
+    let parents = merge
+        .parent_ids()
+        .enumerate()
+        .skip(1)
+        .map(|(index, parent)| Target {
+            name: format!("HEAD^{}", index + 1),
+            oid: parent,
+        })
+        .collect();
+    Ok(Some(parents))
+}
+
+/// Keys, threshold and attestations revisions are verified against.
+struct Policy {
+    /// The public keys allowed to sign.
+    keys: BTreeMap<PathBuf, PublicKey>,
+    /// Minimum number of keys whose signature must verify.
+    threshold: usize,
+    /// Types of attestations required by one of the keys.
+    attestations: Vec<String>,
+}
+
+/// Refuse keyrings stored in the work tree, which the checked-out or
+/// merged revisions may have changed to vouch for themselves.
+fn check_trusted_keyring(repo: &Repository, key_path: &Path) -> Result<()> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok(());
+    };
+    let key_path = key_path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve keyring {}", key_path.display()))?;
+    let workdir = workdir
+        .canonicalize()
+        .context("Failed to resolve the work tree")?;
+    let git_dir = repo
+        .path()
+        .canonicalize()
+        .context("Failed to resolve the git directory")?;
+    anyhow::ensure!(
+        !key_path.starts_with(&workdir) || key_path.starts_with(&git_dir),
+        "Refusing to verify revisions with the keyring {} from the work tree they \
+         changed, store it outside of the work tree or in the git directory",
+        key_path.display()
+    );
+    Ok(())
+}
+
+/// Check the signatures and attestations over `target`, and describe
+/// what fails to verify.
+fn check_target(repo: &Repository, policy: &Policy, target: &Target) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+
+    // keys stored in both the signify and minisign formats count once
+    let mut signers = HashSet::new();
+    for (path, public_key) in &policy.keys {
+        match verify_target(repo, public_key, target.oid)? {
+            (VerificationStatus::Valid, _) => {
+                signers.insert(public_key.identity()?);
+            }
+            (VerificationStatus::Missing, _) => {}
+            (VerificationStatus::Invalid, error) => problems.push(format!(
+                "{} ({}: invalid signature by {}: {})",
+                target.name,
+                target.oid,
+                path.display(),
+                error.unwrap_or_default()
+            )),
+        }
+    }
+
+    let threshold = policy.threshold;
+    let verified_keys = signers.len();
+    if verified_keys < threshold {
+        problems.push(format!(
+            "{} ({}: {verified_keys} out of the required {threshold} keys signed it)",
+            target.name, target.oid
+        ));
+    }
+
+    for kind in &policy.attestations {
+        let mut attested = false;
+        for attested_oid in peeled_oids(repo, target.oid) {
+            let attesters =
+                attest::attested_by(repo, &policy.keys, kind, attested_oid, |refname| {
+                    repo.refname_to_id(refname).ok()
+                })?;
+            attested |= !attesters.is_empty();
+        }
+        if !attested {
+            problems.push(format!(
+                "{} ({}: no {kind} attestation)",
+                target.name, target.oid
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Collect `oid`, along with the commit it peels to, if any.
+fn peeled_oids(repo: &Repository, oid: Oid) -> Vec<Oid> {
+    let mut oids = vec![oid];
+    if let Ok(commit) = repo
+        .find_object(oid, None)
+        .and_then(|object| object.peel_to_commit())
+    {
+        if commit.id() != oid {
+            oids.push(commit.id());
+        }
+    }
+    oids
+}
+
+/// Collect the tags listed in `FETCH_HEAD` by the last fetch.
+fn fetched_tags(repo: &Repository) -> Result<Vec<Target>> {
+    let mut targets = Vec::new();
+
+    // FETCH_HEAD is missing until the first fetch
+    if !repo.path().join("FETCH_HEAD").exists() {
+        return Ok(targets);
+    }
+
+    repo.fetchhead_foreach(|refname, _, &oid, _| {
+        if let Some(tag) = refname.strip_prefix("refs/tags/") {
+            targets.push(Target {
+                name: tag.to_owned(),
+                oid,
+            });
+        }
+        true
+    })
+    .context("Failed to read FETCH_HEAD")?;
+
+    Ok(targets)
+}
+
+/// Verify the signature of `public_key` over `oid`, or over the commit
+/// it peels to. Returns the reason verification failed, if it did.
+fn verify_target(
+    repo: &Repository,
+    public_key: &PublicKey,
+    oid: Oid,
+) -> Result<(VerificationStatus, Option<String>)> {
+    let mut result = (VerificationStatus::Missing, None);
+
+    for signed_oid in peeled_oids(repo, oid) {
+        for key_fingerprint in public_key.equivalent_fingerprints()? {
+            let refname = utils::craft_signature_reference(key_fingerprint, signed_oid);
+            let Ok(signature_oid) = repo.refname_to_id(&refname) else {
+                continue;
+            };
+            match utils::verify_signature_over(repo, public_key, signature_oid, signed_oid) {
+                Ok(()) => return Ok((VerificationStatus::Valid, None)),
+                Err(e) => result = (VerificationStatus::Invalid, Some(format!("{e:#}"))),
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::{test_commit, test_repository};
+
+    /// Check out `base`, then move `HEAD` to a merge of `base` and
+    /// another commit, recording `message` in the reflog.
+    fn merge_into_head(repo: &Repository, message: &str) -> Oid {
+        let base = repo.find_commit(test_commit(repo, "base", &[])).unwrap();
+        let topic = repo.find_commit(test_commit(repo, "topic", &[])).unwrap();
+        let merge = test_commit(repo, "merge", &[&base, &topic]);
+        repo.reference("HEAD", base.id(), true, "checkout: base")
+            .unwrap();
+        repo.reference("HEAD", merge, true, message).unwrap();
+        merge
+    }
+
+    fn unsigned_policy() -> Policy {
+        Policy {
+            keys: BTreeMap::new(),
+            threshold: 1,
+            attestations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn upstream_merge_must_be_signed() {
+        let repo = test_repository("upstream-merge");
+        let merge = merge_into_head(&repo, "pull: Fast-forward");
+
+        assert!(local_merge_parents(&repo, merge).unwrap().is_none());
+        let problems = check_head(&repo, &unsigned_policy()).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("HEAD ("), "{problems:?}");
+    }
+
+    #[test]
+    fn local_merge_checks_merged_in_tips() {
+        let repo = test_repository("local-merge");
+        let merge = merge_into_head(&repo, "merge topic: Merge made by the 'ort' strategy.");
+
+        let parents = local_merge_parents(&repo, merge).unwrap().unwrap();
+        assert_eq!(parents.len(), 1);
+        assert_eq!(parents[0].name, "HEAD^2");
+        let problems = check_head(&repo, &unsigned_policy()).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("HEAD^2 ("), "{problems:?}");
+    }
+}
@@ -0,0 +1,112 @@
+//! Install git hooks running `git-signify`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+
+use crate::utils;
+
+/// Git hook implemented by `git-signify`.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Hook {
+    /// Push signatures along with the objects they sign
+    PrePush,
+    /// Reject pushes of protected refs to unsigned objects
+    PreReceive,
+    /// Verify `HEAD` after checking out a branch
+    PostCheckout,
+    /// Verify `HEAD` after merging, e.g. on `git pull`
+    PostMerge,
+}
+
+impl Hook {
+    /// Name of the hook, as known to git.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::PrePush => "pre-push",
+            Self::PreReceive => "pre-receive",
+            Self::PostCheckout => "post-checkout",
+            Self::PostMerge => "post-merge",
+        }
+    }
+
+    /// The `git signify hook` subcommand implementing this hook.
+    const fn subcommand(self) -> &'static str {
+        match self {
+            Self::PrePush => "pre-push",
+            Self::PreReceive => "pre-receive",
+            Self::PostCheckout | Self::PostMerge => "verify",
+        }
+    }
+}
+
+/// Execute the `install-hooks` command. Hooks are written to
+/// `hooks_path`, or to the `hooks` directory of the repository.
+/// If `block_unsigned` is set, the `pre-push` hook blocks pushes
+/// of unsigned protected refs.
+pub fn command(
+    hooks: Vec<Hook>,
+    block_unsigned: bool,
+    force: bool,
+    hooks_path: Option<PathBuf>,
+) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let hooks_dir = match hooks_path {
+        Some(hooks_path) => hooks_path,
+        // linked worktrees share the hooks of `<common dir>/worktrees/<name>/../..`
+        None if repo.is_worktree() => repo.path().join("../../hooks"),
+        None => repo.path().join("hooks"),
+    };
+
+    fs::create_dir_all(&hooks_dir)
+        .with_context(|| format!("Failed to create {}", hooks_dir.display()))?;
+
+    let hooks = if hooks.is_empty() {
+        vec![if repo.is_bare() {
+            Hook::PreReceive
+        } else {
+            Hook::PrePush
+        }]
+    } else {
+        hooks
+    };
+
+    for hook in &hooks {
+        let path = hooks_dir.join(hook.name());
+        anyhow::ensure!(
+            force || !path.exists(),
+            "Hook {} already exists, pass --force to overwrite it",
+            path.display()
+        );
+    }
+
+    println!("Installed hooks:");
+
+    for hook in hooks {
+        let path = hooks_dir.join(hook.name());
+
+        let flags = match hook {
+            Hook::PrePush if block_unsigned => " --block-unsigned",
+            _ => "",
+        };
+        let script = format!(
+            "#!/bin/sh\nexec git signify hook {}{flags} \"$@\"\n",
+            hook.subcommand()
+        );
+
+        utils::write_file_atomically(&path, script.as_bytes(), false)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+                .with_context(|| format!("Failed to make {} executable", path.display()))?;
+        }
+
+        println!("  - {}", path.display());
+    }
+
+    Ok(())
+}
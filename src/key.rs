@@ -0,0 +1,4 @@
+//! Manage git-signify keys.
+
+pub mod convert;
+pub mod passwd;
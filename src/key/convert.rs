@@ -0,0 +1,69 @@
+//! Convert keys between the signify and minisign formats.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+
+use crate::utils;
+
+/// Formats keys can be converted to.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum KeyFormat {
+    /// Signify key.
+    Signify,
+    /// Minisign key.
+    Minisign,
+}
+
+impl From<KeyFormat> for utils::TreeSignatureAlgo {
+    fn from(format: KeyFormat) -> Self {
+        match format {
+            KeyFormat::Signify => Self::Signify,
+            KeyFormat::Minisign => Self::Minisign,
+        }
+    }
+}
+
+/// Execute the `key convert` command.
+pub fn command(
+    secret: bool,
+    format: KeyFormat,
+    force: bool,
+    key_path: PathBuf,
+    output_path: PathBuf,
+) -> Result<()> {
+    if !force && output_path.exists() {
+        anyhow::bail!(
+            "Refusing to overwrite existing key {}",
+            output_path.display()
+        );
+    }
+
+    if secret {
+        let (secret_key, passphrase) = utils::read_secret_key(&key_path)?;
+        let converted_key = secret_key.convert(format.into())?;
+        // unencrypted keys are still prompted for a passphrase, to which
+        // an empty answer keeps them unencrypted
+        let passphrase = passphrase
+            .as_ref()
+            .map(|p| p.as_str())
+            .filter(|p| !p.is_empty());
+        let encoded_key = utils::encode_secret_key(&converted_key, passphrase)?;
+        utils::write_file_atomically(&output_path, encoded_key.as_bytes(), true)
+            .context("Failed to write converted secret key")?;
+    } else {
+        let public_key = utils::get_public_keys(key_path.clone())?
+            .remove(&key_path)
+            .context("Expected the path to a single public key")?;
+        let converted_key = public_key.convert(format.into())?;
+        let encoded_key = utils::encode_public_key(&converted_key)?;
+        utils::write_file_atomically(&output_path, encoded_key.as_bytes(), false)
+            .context("Failed to write converted public key")?;
+    }
+
+    println!("Converted key written to:");
+    println!("  - {}", output_path.display());
+
+    Ok(())
+}
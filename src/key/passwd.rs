@@ -0,0 +1,46 @@
+//! Change the passphrase of secret keys.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use zeroize::Zeroizing;
+
+use crate::utils;
+
+/// Execute the `key passwd` command.
+pub fn command(key_path: PathBuf) -> Result<()> {
+    let (secret_key, _) = utils::read_secret_key(&key_path)?;
+
+    if let utils::PrivateKey::External(_) = &secret_key {
+        anyhow::bail!("Secret keys of external signers cannot be re-encrypted");
+    }
+
+    let passphrase = prompt_new_passphrase()?;
+    let encoded_key = utils::encode_secret_key(
+        &secret_key,
+        (!passphrase.is_empty()).then(|| passphrase.as_str()),
+    )?;
+
+    utils::write_file_atomically(&key_path, encoded_key.as_bytes(), true)
+        .context("Failed to write re-encrypted secret key")?;
+
+    println!("Passphrase changed for key:");
+    println!("  - {}", key_path.display());
+
+    Ok(())
+}
+
+/// Prompt for a new passphrase, twice. An empty passphrase leaves
+/// the key unencrypted.
+fn prompt_new_passphrase() -> Result<Zeroizing<String>> {
+    let passphrase = rpassword::prompt_password("New passphrase (empty for none): ")
+        .map(Zeroizing::new)
+        .context("Failed to read new passphrase")?;
+    let confirmation = rpassword::prompt_password("Confirm new passphrase: ")
+        .map(Zeroizing::new)
+        .context("Failed to read new passphrase confirmation")?;
+
+    anyhow::ensure!(passphrase == confirmation, "Passphrases do not match");
+
+    Ok(passphrase)
+}
@@ -1,117 +1,463 @@
 //! List signatures stored in this repository.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
 
 use anyhow::{Context, Result};
-use git2::{Direction, Oid, ProxyOptions, Remote, Repository};
+use clap::ValueEnum;
+use git2::{ObjectType, Oid, Repository, Sort};
+use serde::Serialize;
+
+use super::filter;
+use super::output::{self, Format, ObjectInfo, VerificationStatus};
+use super::remote::{self, RemoteRef};
+use super::utils::{self, Keyring, SignatureStatus};
+
+/// Type of a signed git object.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ObjectKind {
+    /// Commit objects
+    Commit,
+    /// Annotated tag objects
+    Tag,
+    /// Tree objects
+    Tree,
+    /// Blob objects
+    Blob,
+}
 
-use super::utils;
+impl From<ObjectKind> for ObjectType {
+    fn from(kind: ObjectKind) -> Self {
+        match kind {
+            ObjectKind::Commit => Self::Commit,
+            ObjectKind::Tag => Self::Tag,
+            ObjectKind::Tree => Self::Tree,
+            ObjectKind::Blob => Self::Blob,
+        }
+    }
+}
 
-/// Execute the `list-signatures` command.
-pub fn command(output_json: bool, remote: Option<String>) -> Result<()> {
-    let repo = utils::open_repository()?;
+/// Command line arguments of the `list-signatures` command.
+#[derive(clap::Args)]
+pub struct ListArgs {
+    /// Output JSON, in the unversioned format of earlier
+    /// releases; prefer `--format json`
+    #[arg(long)]
+    json: bool,
+
+    /// The name of the remote repository, to
+    /// list remote signatures
+    #[arg(short = 'R', long)]
+    remote: Option<String>,
+
+    /// The path to the base64 encoded public keys to verify
+    /// the listed signatures with
+    #[arg(short = 'k', long)]
+    keyring: Option<PathBuf>,
+
+    /// Only list signatures made by the key with the given
+    /// fingerprint, may be repeated
+    #[arg(long = "fingerprint")]
+    fingerprints: Vec<String>,
+
+    /// Only list signatures made by the public keys at the
+    /// given path, may be repeated
+    #[arg(long = "signer")]
+    signers: Vec<PathBuf>,
+
+    /// Only list signatures over objects of the given type,
+    /// may be repeated
+    #[arg(long = "type", value_enum)]
+    kinds: Vec<ObjectKind>,
+
+    /// Only list signatures over the given revision, or over
+    /// the commits in the given range (e.g. `v1.0..v2.0`), may
+    /// be repeated
+    #[arg(short = 'r', long = "rev")]
+    revisions: Vec<String>,
+
+    /// Only name signed objects after the references pointing
+    /// directly at them, rather than describing them relative
+    /// to the nearest reference, which walks their history
+    #[arg(long)]
+    no_describe: bool,
+}
 
-    if let Some(remote_name) = remote {
-        let mut remote = repo
-            .find_remote(&remote_name)
-            .context("Unable to find remote")?;
+/// Signed object, as described in JSON output.
+#[derive(Serialize)]
+struct SignedObject {
+    /// The signed object.
+    #[serde(flatten)]
+    object: ObjectInfo,
+    /// Name of the signed object, as described by git.
+    name: String,
+    /// Fingerprints of the keys that signed the object.
+    signers: Vec<String>,
+    /// Verification results of each signature, if a keyring was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verifications: Option<Vec<SignerVerification>>,
+}
 
-        let proxy_options = {
-            let mut opts = ProxyOptions::new();
-            opts.auto();
-            opts
-        };
+/// Verification result of a signature, as described in JSON output.
+#[derive(Serialize)]
+struct SignerVerification {
+    /// Fingerprint of the signer.
+    fingerprint: String,
+    /// Name of the key of the signer, if it is in the keyring.
+    key_name: Option<String>,
+    /// The outcome of the verification, or [`None`] if the
+    /// key of the signer is not in the keyring.
+    status: Option<VerificationStatus>,
+    /// The reason why verification failed, if it did.
+    error: Option<String>,
+}
 
-        remote
-            .connect_auth(Direction::Fetch, None, Some(proxy_options))
-            .with_context(|| {
-                format!(
-                    "Failed to connect to remote {remote_name}, only \
-                     remotes with no authentication are supported",
-                )
-            })?;
+/// Results of the `list-signatures` command, as described in JSON output.
+#[derive(Serialize)]
+struct Listing<'remote> {
+    /// The remote the signatures were listed from, if any.
+    remote: Option<&'remote str>,
+    /// Whether the signers are only named by the references the remote
+    /// advertises, without their signatures being checked.
+    unverified: bool,
+    /// The signed objects.
+    signatures: Vec<SignedObject>,
+}
+
+/// Signatures selected for listing.
+struct Selection {
+    /// Fingerprints of the selected signers, if any were given.
+    signers: Option<BTreeSet<Oid>>,
+    /// Selected object types. Empty if all types are selected.
+    kinds: Vec<ObjectType>,
+    /// Ids of the selected signed objects, if any were given.
+    objects: Option<BTreeSet<Oid>>,
+}
+
+/// Execute the `list-signatures` command. If `--json` is passed, the
+/// unversioned JSON output of earlier releases is produced.
+pub fn command(format: Format, args: ListArgs) -> Result<()> {
+    let repo = utils::open_repository()?;
 
-        command_inner(&repo, output_json, &remote)
+    let selection = Selection {
+        signers: filter::resolve_signers(&args.fingerprints, args.signers)?,
+        kinds: args.kinds.into_iter().map(ObjectType::from).collect(),
+        objects: filter::resolve_revisions(&repo, &args.revisions)?,
+    };
+    let keyring = args.keyring.map(Keyring::open).transpose()?;
+
+    let signers = if let Some(remote_name) = args.remote.as_deref() {
+        anyhow::ensure!(
+            keyring.is_none(),
+            "Signatures listed from a remote cannot be verified, pull them first"
+        );
+        let remote_refs = remote::list(&repo, remote_name)?;
+        selection.apply(&repo, remote_refs[..].find_signers()?)
     } else {
-        command_inner(&repo, output_json, &repo)
+        selection.apply(&repo, repo.find_signers()?)
+    };
+
+    let mut names = RefIndex::build(&repo)?;
+    if !args.no_describe {
+        names.describe(&repo, signers.keys().copied())?;
     }
-}
 
-fn command_inner<'repo, F: FindSigners + ?Sized>(
-    repo: &'repo Repository,
-    output_json: bool,
-    find_signers: &'repo F,
-) -> Result<()> {
-    if !output_json {
-        output_signers_human(repo, find_signers)
+    let listing = Signers {
+        repo: &repo,
+        keyring: keyring.as_ref(),
+        names,
+        signers,
+        unverified: args.remote.is_some(),
+    };
+
+    if format.is_json() {
+        listing.output_json(args.remote.as_deref())
+    } else if args.json {
+        listing.output_legacy_json()
     } else {
-        output_signers_json(repo, find_signers)
+        listing.output_human()
     }
 }
 
-fn output_signers_human<F: FindSigners + ?Sized>(repo: &Repository, f: &F) -> Result<()> {
-    for (oid, signers) in f.find_signers()? {
-        let signed_rev = describe_object(repo, oid)?;
-        println!("Signers of {signed_rev}:");
-
-        for signer in signers {
-            println!("  - {signer}");
-        }
+impl Selection {
+    /// Filter the signers of each object.
+    fn apply(
+        &self,
+        repo: &Repository,
+        mut signers: BTreeMap<Oid, Vec<Oid>>,
+    ) -> BTreeMap<Oid, Vec<Oid>> {
+        signers.retain(|oid, object_signers| {
+            if let Some(selected) = &self.signers {
+                object_signers.retain(|signer| selected.contains(signer));
+            }
+            !object_signers.is_empty()
+                && self
+                    .objects
+                    .as_ref()
+                    .is_none_or(|objects| objects.contains(oid))
+                && (self.kinds.is_empty()
+                    || repo
+                        .find_object(*oid, None)
+                        .ok()
+                        .and_then(|object| object.kind())
+                        .is_some_and(|kind| self.kinds.contains(&kind)))
+        });
+        signers
     }
-    Ok(())
 }
 
-fn output_signers_json<F: FindSigners + ?Sized>(repo: &Repository, f: &F) -> Result<()> {
-    fn print_signers(signers: Vec<Oid>) {
-        let mut signers_iter = signers.into_iter();
+/// Signers of the listed objects.
+struct Signers<'repo> {
+    repo: &'repo Repository,
+    keyring: Option<&'repo Keyring>,
+    names: RefIndex,
+    signers: BTreeMap<Oid, Vec<Oid>>,
+    /// Whether the signers were listed from a remote, whose signatures
+    /// cannot be checked.
+    unverified: bool,
+}
 
-        print!("[");
-        if let Some(signer) = signers_iter.next() {
-            print!("\"{signer}\"");
+impl Signers<'_> {
+    fn output_human(&self) -> Result<()> {
+        for (&oid, signers) in &self.signers {
+            let signed_rev = self.names.name(oid);
+            println!("Signers of {signed_rev}:");
+
+            for &signer in signers {
+                let Some(verification) = self.verify(oid, signer) else {
+                    if self.unverified {
+                        println!("  - {signer} (unverified)");
+                    } else {
+                        println!("  - {signer}");
+                    }
+                    continue;
+                };
+                let error = verification.error.unwrap_or_default();
+                match (verification.status, verification.key_name) {
+                    (None, _) => println!("  - {signer} (unknown key)"),
+                    (Some(VerificationStatus::Valid), Some(name)) => {
+                        println!("  - {signer} ({name}, valid)");
+                    }
+                    (Some(_), Some(name)) => println!("  - {signer} ({name}, invalid: {error})"),
+                    (Some(_), None) => println!("  - {signer} (invalid: {error})"),
+                }
+            }
         }
-        for signer in signers_iter {
-            print!(",\"{signer}\"");
+        Ok(())
+    }
+
+    fn output_json(&self, remote: Option<&str>) -> Result<()> {
+        let mut signatures = Vec::new();
+
+        for (&oid, signers) in &self.signers {
+            signatures.push(SignedObject {
+                object: ObjectInfo::new(self.repo, oid),
+                name: self.names.name(oid),
+                signers: signers.iter().map(Oid::to_string).collect(),
+                verifications: self.keyring.map(|_| {
+                    signers
+                        .iter()
+                        .filter_map(|&signer| self.verify(oid, signer))
+                        .collect()
+                }),
+            });
         }
-        print!("]");
+
+        output::print_json(
+            "list-signatures",
+            &Listing {
+                remote,
+                unverified: self.unverified,
+                signatures,
+            },
+        )
     }
 
-    let mut objs_iter = f.find_signers()?.into_iter();
+    /// Output a map of signed object names to the fingerprints of their
+    /// signers, as `--json` did before `--format json` was introduced.
+    fn output_legacy_json(&self) -> Result<()> {
+        let mut objects = serde_json::Map::new();
+
+        for (&oid, signers) in &self.signers {
+            let signers = signers
+                .iter()
+                .map(|signer| signer.to_string().into())
+                .collect();
+            objects.insert(self.names.name(oid), serde_json::Value::Array(signers));
+        }
+
+        let json = serde_json::to_string(&objects).context("Failed to serialize JSON output")?;
+        print!("{json}");
 
-    print!("{{");
-    if let Some((oid, signers)) = objs_iter.next() {
-        let signed_rev = describe_object(repo, oid)?;
-        print!("\"{signed_rev}\":");
-        print_signers(signers);
+        Ok(())
     }
-    for (oid, signers) in objs_iter {
-        let signed_rev = describe_object(repo, oid)?;
-        print!(",\"{signed_rev}\":");
-        print_signers(signers);
+
+    /// Verify the signature by `signer` over `oid`, if a keyring was given.
+    fn verify(&self, oid: Oid, signer: Oid) -> Option<SignerVerification> {
+        let keyring = self.keyring?;
+        let refname = utils::craft_signature_reference(signer, oid);
+
+        let (key_name, status, error) = match self.repo.refname_to_id(&refname) {
+            Err(e) => (None, Some(VerificationStatus::Missing), Some(e.to_string())),
+            Ok(signature_oid) => {
+                match utils::check_signature(self.repo, keyring, signer, oid, signature_oid) {
+                    SignatureStatus::Valid { key_path } => (
+                        Some(key_name(key_path)),
+                        Some(VerificationStatus::Valid),
+                        None,
+                    ),
+                    SignatureStatus::Invalid(e) => (
+                        keyring.get(signer).map(|(key_path, _)| key_name(key_path)),
+                        Some(VerificationStatus::Invalid),
+                        Some(format!("{e:#}")),
+                    ),
+                    SignatureStatus::UnknownKey => (None, None, None),
+                }
+            }
+        };
+
+        Some(SignerVerification {
+            fingerprint: signer.to_string(),
+            key_name,
+            status,
+            error,
+        })
     }
-    print!("}}");
+}
 
-    Ok(())
+/// Name of a key in a keyring, i.e. its file name without extension.
+fn key_name(key_path: &std::path::Path) -> String {
+    key_path
+        .file_stem()
+        .unwrap_or(key_path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
 }
 
-fn describe_object(repo: &Repository, oid: Oid) -> Result<String> {
-    let Ok(object) = repo.find_object(oid, None) else {
-        return Ok(oid.to_string());
-    };
+/// Index of the names of the references in a repository, by the
+/// objects they point at. Built once and reused to name all listed
+/// objects, rather than describing each of them separately, which
+/// walks the history of the repository every time.
+struct RefIndex {
+    /// Names of the indexed references, by priority.
+    refs: Vec<String>,
+    /// Index of [`RefIndex::refs`] by the objects they point at.
+    by_oid: HashMap<Oid, usize>,
+    /// Descriptions of commits no reference points at, relative to
+    /// the nearest reference, like `git describe --all` would do.
+    descriptions: HashMap<Oid, String>,
+}
 
-    let opts = {
-        let mut opts = git2::DescribeOptions::new();
-        opts.describe_all();
-        opts.show_commit_oid_as_fallback(true);
-        opts
-    };
+impl RefIndex {
+    /// Index the references of `repo`, other than those created by
+    /// `git-signify`. Tags take precedence over branches, which take
+    /// precedence over remote-tracking branches and other references.
+    fn build(repo: &Repository) -> Result<Self> {
+        let mut refs = Vec::new();
 
-    let description = object
-        .describe(&opts)
-        .with_context(|| format!("Failed to describe oid={oid}"))?;
+        for maybe_ref in repo
+            .references()
+            .context("Failed to look-up git references")?
+        {
+            let reference = maybe_ref.context("Failed to parse git reference")?;
+            let Some(refname) = reference.name() else {
+                continue;
+            };
+            if refname.starts_with("refs/signify/") {
+                continue;
+            }
+            let Some(target) = reference.target() else {
+                continue;
+            };
+            let peeled = reference.peel_to_commit().ok().map(|commit| commit.id());
+            let name = refname.strip_prefix("refs/").unwrap_or(refname).to_owned();
+            refs.push((name, target, peeled));
+        }
+
+        refs.sort_by_key(|(name, _, _)| {
+            let priority = ["tags/", "heads/", "remotes/"]
+                .iter()
+                .position(|prefix| name.starts_with(prefix))
+                .unwrap_or(3);
+            (priority, name.clone())
+        });
+
+        let mut by_oid = HashMap::new();
+        for (index, (_, target, peeled)) in refs.iter().enumerate() {
+            by_oid.entry(*target).or_insert(index);
+            if let Some(peeled) = peeled {
+                by_oid.entry(*peeled).or_insert(index);
+            }
+        }
 
-    description
-        .format(None)
-        .with_context(|| format!("Failed to format description of oid={oid}"))
+        Ok(Self {
+            refs: refs.into_iter().map(|(name, _, _)| name).collect(),
+            by_oid,
+            descriptions: HashMap::new(),
+        })
+    }
+
+    /// Describe the given commits no reference points at, relative to
+    /// their nearest ancestor a reference points at, in a single walk
+    /// over their history.
+    fn describe<I: IntoIterator<Item = Oid>>(&mut self, repo: &Repository, oids: I) -> Result<()> {
+        let mut walk = repo.revwalk().context("Failed to walk revisions")?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)
+            .context("Failed to walk revisions")?;
+
+        let mut undescribed = HashSet::new();
+        for oid in oids {
+            if self.by_oid.contains_key(&oid) || repo.find_commit(oid).is_err() {
+                continue;
+            }
+            walk.push(oid).context("Failed to walk revisions")?;
+            undescribed.insert(oid);
+        }
+        if undescribed.is_empty() {
+            return Ok(());
+        }
+
+        // parents are visited before their children, so the nearest
+        // reference of each commit is known once its children are visited
+        let mut nearest: HashMap<Oid, (usize, usize)> = HashMap::new();
+
+        for maybe_oid in walk {
+            let oid = maybe_oid.context("Failed to walk revisions")?;
+            let near = if let Some(&index) = self.by_oid.get(&oid) {
+                Some((0, index))
+            } else {
+                repo.find_commit(oid)
+                    .context("Failed to look-up commit")?
+                    .parent_ids()
+                    .filter_map(|parent| nearest.get(&parent))
+                    .min()
+                    .map(|&(distance, index)| (distance + 1, index))
+            };
+            let Some((distance, index)) = near else {
+                continue;
+            };
+            nearest.insert(oid, (distance, index));
+            if undescribed.contains(&oid) {
+                let short = &oid.to_string()[..7];
+                self.descriptions
+                    .insert(oid, format!("{}-{distance}-g{short}", self.refs[index]));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Name the object `oid`, after a reference pointing at it, or after
+    /// its description. Falls back to the object id.
+    fn name(&self, oid: Oid) -> String {
+        if let Some(&index) = self.by_oid.get(&oid) {
+            return self.refs[index].clone();
+        }
+        self.descriptions
+            .get(&oid)
+            .cloned()
+            .unwrap_or_else(|| oid.to_string())
+    }
 }
 
 trait FindSigners {
@@ -132,6 +478,16 @@ impl FindSigners for Repository {
             let Some((oid, signer)) = parse_signature_oid_and_signer(revname) else {
                 continue;
             };
+            let Some(signature_oid) = rev.target() else {
+                continue;
+            };
+
+            // reference names are not authenticated, so make sure
+            // the signature really is over the object they name
+            if let Err(e) = utils::TreeSignature::load_oid_over(self, signature_oid, oid) {
+                eprintln!("Ignoring invalid signature stored under {revname}: {e:#}");
+                continue;
+            }
 
             signers.entry(oid).or_default().push(signer);
         }
@@ -140,15 +496,16 @@ impl FindSigners for Repository {
     }
 }
 
-impl FindSigners for Remote<'_> {
+/// Signers named by the signature references a remote advertises. The
+/// signatures have not been fetched, so they are neither checked to be
+/// over the objects their references name, nor verified.
+impl FindSigners for [RemoteRef] {
     fn find_signers(&self) -> Result<BTreeMap<Oid, Vec<Oid>>> {
         let mut signers: BTreeMap<_, Vec<_>> = BTreeMap::new();
 
         for (oid, signer) in self
-            .list()
-            .context("Failed to look-up remote refs")?
             .iter()
-            .filter_map(|head| parse_signature_oid_and_signer(head.name()))
+            .filter_map(|remote_ref| parse_signature_oid_and_signer(&remote_ref.name))
         {
             signers.entry(oid).or_default().push(signer);
         }
@@ -161,10 +518,7 @@ fn parse_signature_oid_and_signer(revname: &str) -> Option<(Oid, Oid)> {
     let ("", signer_and_oid) = revname.split_once(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)? else {
         return None;
     };
-    let (signer, oid) = signer_and_oid.split_once('/')?;
-
-    let oid = Oid::from_str(oid).ok()?;
-    let signer = Oid::from_str(signer).ok()?;
+    let (signer, oid) = utils::parse_signer_and_oid(signer_and_oid)?;
 
     Some((oid, signer))
 }
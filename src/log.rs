@@ -0,0 +1,6 @@
+//! Append-only transparency log of signatures.
+
+pub mod append;
+pub mod entry;
+pub mod missing;
+pub mod verify;
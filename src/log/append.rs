@@ -0,0 +1,55 @@
+//! Append signatures to the transparency log.
+
+use anyhow::{Context, Result};
+
+use super::entry::{self, Entry};
+use crate::utils;
+
+/// Execute the `log append` command. Appends the signatures stored
+/// under `refnames`, or under every signature reference if none are
+/// given, unless they already appear in the log.
+pub fn command(refnames: Vec<String>) -> Result<()> {
+    let repo = utils::open_repository()?;
+
+    let mut entries = Vec::new();
+    if refnames.is_empty() {
+        for maybe_ref in repo
+            .references_glob(utils::ALL_SIGNIFY_SIGNATURE_REFS)
+            .context("Failed to look-up signature refs")?
+        {
+            let reference = maybe_ref.context("Failed to parse git reference")?;
+            if let (Some(refname), Some(signature_oid)) = (reference.name(), reference.target()) {
+                entries.extend(Entry::from_ref(refname, signature_oid));
+            }
+        }
+    } else {
+        for refname in &refnames {
+            let signature_oid = repo
+                .refname_to_id(refname)
+                .with_context(|| format!("Failed to look-up {refname}"))?;
+            entries.push(
+                Entry::from_ref(refname, signature_oid)
+                    .with_context(|| format!("{refname} is not a signature reference"))?,
+            );
+        }
+    }
+    entries.sort();
+
+    let appended = entry::append(&repo, &entries)?;
+
+    if appended.is_empty() {
+        println!("The log is up to date");
+        return Ok(());
+    }
+
+    println!("Appended to the log:");
+    for entry in &appended {
+        println!(
+            "  - {} ({})",
+            utils::craft_signature_reference(entry.signer, entry.object),
+            entry.signature
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Entries of the transparency log, and appending to it.
+//!
+//! The log is a linear series of commits under [`LOG_REF`], each of
+//! which records a single signature in an `entry` blob:
+//!
+//! ```text
+//! signer <fingerprint>
+//! object <signed oid>
+//! signature <signature oid>
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+
+use anyhow::{Context, Result};
+use git2::{Commit, ErrorCode, Oid, Repository};
+
+use crate::utils;
+
+/// Git reference pointing at the latest entry of the transparency log.
+pub const LOG_REF: &str = "refs/signify/log";
+
+/// Signature recorded in the transparency log.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entry {
+    /// Fingerprint of the signer.
+    pub signer: Oid,
+    /// The signed object.
+    pub object: Oid,
+    /// The signature commit, or tree for v0 signatures.
+    pub signature: Oid,
+}
+
+impl Entry {
+    /// Build the entry of the signature `signature` stored under the
+    /// signature reference `refname`.
+    pub fn from_ref(refname: &str, signature: Oid) -> Option<Self> {
+        let (signer, object) = refname
+            .strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+            .and_then(utils::parse_signer_and_oid)?;
+        Some(Self {
+            signer,
+            object,
+            signature,
+        })
+    }
+
+    /// Parse the `entry` blob of a log commit.
+    pub fn parse(contents: &[u8]) -> Result<Self> {
+        let contents = std::str::from_utf8(contents).context("Log entry is not UTF-8")?;
+        let mut fields = [None; 3];
+        for line in contents.lines() {
+            let (key, value) = line
+                .split_once(' ')
+                .with_context(|| format!("Malformed log entry line {line:?}"))?;
+            let index = match key {
+                "signer" => 0,
+                "object" => 1,
+                "signature" => 2,
+                _ => anyhow::bail!("Unknown log entry field {key:?}"),
+            };
+            anyhow::ensure!(fields[index].is_none(), "Duplicate log entry field {key:?}");
+            fields[index] =
+                Some(Oid::from_str(value).with_context(|| format!("Invalid object id {value}"))?);
+        }
+        let [Some(signer), Some(object), Some(signature)] = fields else {
+            anyhow::bail!("Incomplete log entry");
+        };
+        Ok(Self {
+            signer,
+            object,
+            signature,
+        })
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "signer {}", self.signer)?;
+        writeln!(f, "object {}", self.object)?;
+        writeln!(f, "signature {}", self.signature)
+    }
+}
+
+/// Whether signatures are appended to the log as they are added,
+/// according to `signify.transparencyLog`.
+pub fn enabled(repo: &Repository) -> Result<bool> {
+    let config = repo.config().context("Failed to open git config")?;
+    match config.get_bool("signify.transparencyLog") {
+        Ok(enabled) => Ok(enabled),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(false),
+        Err(e) => Err(e).context("Failed to read signify.transparencyLog from git config"),
+    }
+}
+
+/// Read the entry recorded by the log commit `commit`.
+pub fn read_commit(repo: &Repository, commit: &Commit<'_>) -> Result<Entry> {
+    let tree = commit.tree().context("Failed to read log entry tree")?;
+    anyhow::ensure!(tree.len() == 1, "Log entry tree holds other files");
+    let blob = tree
+        .get_name("entry")
+        .context("Log entry tree has no entry")?
+        .to_object(repo)
+        .context("Failed to read log entry")?
+        .into_blob()
+        .map_err(|_| anyhow::anyhow!("Log entry is not a blob"))?;
+    Entry::parse(blob.content())
+}
+
+/// Read the entries of the log, from the oldest to the newest one.
+pub fn read_all(repo: &Repository) -> Result<Vec<Entry>> {
+    let Ok(tip) = repo.refname_to_id(LOG_REF) else {
+        return Ok(Vec::new());
+    };
+
+    let mut entries = Vec::new();
+    let mut next = Some(tip);
+    while let Some(oid) = next {
+        let commit = repo
+            .find_commit(oid)
+            .with_context(|| format!("Failed to look-up log entry {oid}"))?;
+        entries
+            .push(read_commit(repo, &commit).with_context(|| format!("Invalid log entry {oid}"))?);
+        next = commit.parent_ids().next();
+    }
+
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Append the `entries` missing from the log to it, and return the
+/// ones which were appended.
+pub fn append(repo: &Repository, entries: &[Entry]) -> Result<Vec<Entry>> {
+    let mut logged: HashSet<_> = read_all(repo)?.into_iter().collect();
+    let author = repo
+        .signature()
+        .context("Failed to retrieve commit author")?;
+    let mut parent = match repo.refname_to_id(LOG_REF) {
+        Ok(tip) => Some(repo.find_commit(tip).context("Failed to look-up log tip")?),
+        Err(_) => None,
+    };
+
+    let mut appended = Vec::new();
+    for &entry in entries {
+        if !logged.insert(entry) {
+            continue;
+        }
+
+        let blob = repo
+            .blob(entry.to_string().as_bytes())
+            .context("Failed to write log entry to the object store")?;
+        let mut tree_builder = repo
+            .treebuilder(None)
+            .context("Failed to get a git tree object builder")?;
+        tree_builder
+            .insert("entry", blob, 0o100644)
+            .context("Failed to write entry to the tree")?;
+        let tree_oid = tree_builder
+            .write()
+            .context("Failed to write tree to the object store")?;
+        let tree = repo
+            .find_tree(tree_oid)
+            .context("Failed to look-up newly created log entry tree")?;
+
+        let parents: Vec<_> = parent.iter().collect();
+        let commit_oid = repo
+            .commit(
+                None,
+                &author,
+                &author,
+                &format!(
+                    "git-signify log entry for {}/{}",
+                    entry.signer, entry.object
+                ),
+                &tree,
+                &parents,
+            )
+            .context("Failed to create log entry commit")?;
+        parent = Some(
+            repo.find_commit(commit_oid)
+                .context("Failed to look-up newly created log entry")?,
+        );
+        appended.push(entry);
+    }
+
+    if let Some(tip) = &parent {
+        // the reflog of the log lets `log verify` check that it was
+        // only ever appended to
+        utils::keep_reflog(repo, LOG_REF)?;
+        let message = format!("git-signify log: append {} entries", appended.len());
+        match repo.refname_to_id(LOG_REF) {
+            Ok(previous) if previous != tip.id() => {
+                repo.reference_matching(LOG_REF, tip.id(), true, previous, &message)
+            }
+            Ok(_) => return Ok(appended),
+            Err(_) => repo.reference(LOG_REF, tip.id(), false, &message),
+        }
+        .context("Failed to update the transparency log")?;
+    }
+
+    Ok(appended)
+}
@@ -0,0 +1,51 @@
+//! Find signatures which never appeared in the transparency log.
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+
+use super::entry::{self, Entry};
+use crate::utils;
+
+/// Execute the `log missing` command, failing if any signature stored
+/// under `refs/signify/signatures` is missing from the log.
+pub fn command() -> Result<()> {
+    let repo = utils::open_repository()?;
+    let logged: HashSet<_> = entry::read_all(&repo)?.into_iter().collect();
+
+    let mut checked = 0;
+    let mut missing = Vec::new();
+
+    for maybe_ref in repo
+        .references_glob(utils::ALL_SIGNIFY_SIGNATURE_REFS)
+        .context("Failed to look-up signature refs")?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        let refname = reference.name().context("Invalid reference name")?;
+        let Some(signature_oid) = reference.target() else {
+            continue;
+        };
+        checked += 1;
+
+        let logged =
+            Entry::from_ref(refname, signature_oid).is_some_and(|entry| logged.contains(&entry));
+        if !logged {
+            missing.push(format!("{refname} ({signature_oid})"));
+        }
+    }
+
+    if missing.is_empty() {
+        println!("All {checked} signatures appear in the log");
+        return Ok(());
+    }
+
+    println!("Signatures missing from the log:");
+    for refname in &missing {
+        println!("  - {refname}");
+    }
+
+    anyhow::bail!(
+        "Found {} signatures missing from the log, append them with `git signify log append`",
+        missing.len()
+    )
+}
@@ -0,0 +1,108 @@
+//! Check the consistency of the transparency log.
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use git2::Repository;
+
+use super::entry::{self, LOG_REF};
+use crate::utils::{self, TreeSignature};
+
+/// Execute the `log verify` command. The log must be a linear series
+/// of well-formed entries, each recording a distinct signature over
+/// the object it names, and its reflog must show that it was only
+/// ever appended to.
+pub fn command() -> Result<()> {
+    let repo = utils::open_repository()?;
+    let Ok(tip) = repo.refname_to_id(LOG_REF) else {
+        println!("The log is empty");
+        return Ok(());
+    };
+
+    let mut problems = Vec::new();
+    let mut unavailable = Vec::new();
+    let mut seen = HashSet::new();
+    let mut entries = 0;
+
+    let mut next = Some(tip);
+    while let Some(oid) = next {
+        let commit = repo
+            .find_commit(oid)
+            .with_context(|| format!("Failed to look-up log entry {oid}"))?;
+        next = commit.parent_ids().next();
+        entries += 1;
+
+        if commit.parent_count() > 1 {
+            problems.push(format!("{oid} (merge commits are not log entries)"));
+        }
+
+        let entry = match entry::read_commit(&repo, &commit) {
+            Ok(entry) => entry,
+            Err(e) => {
+                problems.push(format!("{oid} ({e:#})"));
+                continue;
+            }
+        };
+
+        if !seen.insert(entry) {
+            problems.push(format!("{oid} (duplicate of a previous entry)"));
+        }
+
+        if repo.find_object(entry.signature, None).is_err() {
+            unavailable.push(format!("{oid} (signature {})", entry.signature));
+            continue;
+        }
+        match TreeSignature::load_oid(&repo, entry.signature)
+            .and_then(|tree_sig| tree_sig.dereference())
+        {
+            Ok(signed_oid) if signed_oid == entry.object => {}
+            Ok(signed_oid) => problems.push(format!(
+                "{oid} (signature is over {signed_oid}, rather than {})",
+                entry.object
+            )),
+            Err(e) => problems.push(format!("{oid} (failed to load signature: {e:#})")),
+        }
+    }
+
+    problems.extend(rewrites(&repo)?);
+
+    if !unavailable.is_empty() {
+        println!("Entries whose signature is no longer available:");
+        for entry in &unavailable {
+            println!("  - {entry}");
+        }
+    }
+
+    if problems.is_empty() {
+        println!("The log of {entries} entries is consistent");
+        return Ok(());
+    }
+
+    println!("Inconsistent log entries:");
+    for problem in &problems {
+        println!("  - {problem}");
+    }
+
+    anyhow::bail!("Found {} problems in the log", problems.len())
+}
+
+/// Find updates of the log recorded in its reflog which did not
+/// append to it, but rewrote it.
+fn rewrites(repo: &Repository) -> Result<Vec<String>> {
+    let reflog = repo
+        .reflog(LOG_REF)
+        .context("Failed to read the reflog of the log")?;
+
+    let mut rewrites = Vec::new();
+    for reflog_entry in reflog.iter() {
+        let (old, new) = (reflog_entry.id_old(), reflog_entry.id_new());
+        if old.is_zero() || old == new {
+            continue;
+        }
+        if !repo.graph_descendant_of(new, old).unwrap_or(false) {
+            rewrites.push(format!("{new} (rewrote the log from {old})"));
+        }
+    }
+
+    Ok(rewrites)
+}
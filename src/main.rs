@@ -1,25 +1,55 @@
+mod attest;
+mod config;
+mod countersign;
+mod filter;
 mod fingerprint;
+mod fsck;
+mod gpg_shim;
+mod history;
+mod hook;
+mod install_hooks;
+mod key;
 mod list_signatures;
+mod log;
+mod output;
+mod prune;
 mod pull;
 mod push;
 mod raw;
+mod remote;
 mod rev_lookup;
 mod rm;
 mod shell_completions;
 mod sign;
+mod tag;
 mod utils;
 mod verify;
 
-use std::borrow::Cow;
+use std::env;
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 
 /// A git sub-command to sign arbitrary objects
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Run as if started in the given path, rather than in the
+    /// current directory; may be repeated, like in git
+    #[arg(short = 'C', global = true, value_name = "PATH")]
+    directories: Vec<PathBuf>,
+
+    /// The path to the git repository, which may be bare,
+    /// like git's `--git-dir`
+    #[arg(long, global = true, value_name = "PATH")]
+    git_dir: Option<PathBuf>,
+
+    /// The output format of commands which support it, others
+    /// refuse JSON output
+    #[arg(long, global = true, value_enum, default_value_t)]
+    format: output::Format,
+
     /// The action to execute
     #[command(subcommand)]
     action: Action,
@@ -33,60 +63,272 @@ enum Action {
     /// Remove git-signify data
     #[command(subcommand)]
     Rm(RmAction),
+    /// Manage signing keys
+    #[command(subcommand)]
+    Key(KeyAction),
+    /// Run as a git hook
+    #[command(subcommand)]
+    Hook(HookAction),
+    /// Install git hooks running git-signify
+    ///
+    /// Installs the `pre-push` hook by default, or the `pre-receive`
+    /// hook in bare repositories, under `core.hooksPath` or the
+    /// `hooks` directory of the repository.
+    InstallHooks {
+        /// The hook to install, may be repeated
+        #[arg(long = "hook", value_enum)]
+        hooks: Vec<install_hooks::Hook>,
+
+        /// Make the `pre-push` hook block pushes of unsigned
+        /// protected refs
+        #[arg(long)]
+        block_unsigned: bool,
+
+        /// Overwrite existing hooks
+        #[arg(short = 'f', long)]
+        force: bool,
+    },
     /// Hash a key and return it
     Fingerprint {
-        /// The path to the base64 encoded key to hash
+        /// The path to the base64 encoded key to hash,
+        /// defaults to `signify.publicKeys`
         #[arg(short = 'k', long, env = "GIT_KEY_PUB")]
-        key: PathBuf,
+        key: Option<PathBuf>,
     },
     /// Sign an arbitrary object
     Sign {
-        /// The path to the base64 encoded secret key to sign with
+        /// The path to the base64 encoded secret key to sign with,
+        /// defaults to `signify.secretKey`
         #[arg(short = 'k', long, env = "GIT_KEY_SEC")]
-        secret_key: PathBuf,
+        secret_key: Option<PathBuf>,
+
+        /// Sign the target of every ref matching the given glob
+        /// (e.g. `refs/tags/v*`), rather than a single revision
+        #[arg(long = "refs", value_name = "GLOB", conflicts_with = "git_rev")]
+        refs_glob: Option<String>,
+
+        /// Replace existing signatures by the key, which are kept as
+        /// the parent of the new signature commits
+        #[arg(short = 'f', long, visible_alias = "replace")]
+        force: bool,
 
         /// The git revision to sign
+        #[arg(required_unless_present = "refs_glob")]
+        git_rev: Option<String>,
+    },
+    /// Countersign the signatures over some git revision, as a
+    /// witness that saw them
+    ///
+    /// Each countersignature signs a statement naming the signature,
+    /// and the time at which it was made.
+    Countersign {
+        /// The path to the base64 encoded secret key of the witness,
+        /// defaults to `signify.secretKey`
+        #[arg(short = 'k', long, env = "GIT_KEY_SEC")]
+        secret_key: Option<PathBuf>,
+
+        /// Only countersign signatures made by the key with the given
+        /// fingerprint, may be repeated
+        #[arg(long = "fingerprint")]
+        fingerprints: Vec<String>,
+
+        /// Only countersign signatures made by the public keys at the
+        /// given path, may be repeated
+        #[arg(long = "signer")]
+        signers: Vec<PathBuf>,
+
+        /// The signed git revision
+        git_rev: String,
+    },
+    /// Attest a typed claim over some git revision, such as a code
+    /// review or a CI run
+    ///
+    /// Each attestation signs a statement naming the type of the
+    /// claim, the revision, the signer and the time at which it was
+    /// made, along with an optional JSON payload.
+    Attest {
+        /// The path to the base64 encoded secret keys to attest with,
+        /// defaults to `signify.secretKey`
+        #[arg(short = 'k', long, env = "GIT_KEY_SEC")]
+        secret_key: Option<PathBuf>,
+
+        /// The type of the claim, e.g. `code-reviewed`
+        #[arg(long = "type", value_name = "TYPE")]
+        kind: String,
+
+        /// Attach the JSON document at the given path to the claim
+        #[arg(long = "payload", value_name = "PATH")]
+        payload_path: Option<PathBuf>,
+
+        /// Replace existing attestations of the same type by the key
+        #[arg(short = 'f', long)]
+        force: bool,
+
+        /// The git revision to attest
+        #[arg(default_value = "HEAD")]
+        git_rev: String,
+    },
+    /// Create an annotated tag, and sign it
+    Tag {
+        /// The path to the base64 encoded secret keys to sign with,
+        /// defaults to `signify.secretKey`
+        #[arg(short = 'k', long, env = "GIT_KEY_SEC")]
+        secret_key: Option<PathBuf>,
+
+        /// The message of the tag
+        #[arg(short = 'm', long)]
+        message: String,
+
+        /// Replace an existing tag with the same name, removing the
+        /// signatures over the replaced tag, on the remote too with --push
+        #[arg(short = 'f', long)]
+        force: bool,
+
+        /// Push the tag and its signatures to the remote repository
+        #[arg(long)]
+        push: bool,
+
+        /// The name of the remote repository to push to, defaults
+        /// to `signify.defaultRemote` or `origin`
+        #[arg(short = 'R', long, requires = "push")]
+        remote: Option<String>,
+
+        /// The name of the tag
+        name: String,
+
+        /// The git revision to tag
+        #[arg(default_value = "HEAD")]
         git_rev: String,
     },
     /// Verify the signature over some git revision
     Verify {
-        /// The path to the base64 encoded public key to verify with
+        /// The path to the base64 encoded public key to verify with,
+        /// defaults to `signify.publicKeys`
         #[arg(short = 'k', long, env = "GIT_KEY_PUB")]
-        public_key: PathBuf,
+        public_key: Option<PathBuf>,
+
+        /// Minimum number of keys whose signature must verify,
+        /// defaults to `signify.threshold`
+        #[arg(short = 't', long)]
+        threshold: Option<usize>,
+
+        /// Only accept signatures countersigned by one of the witness
+        /// keys at the given path
+        #[arg(long = "witness", value_name = "PATH")]
+        witness_path: Option<PathBuf>,
+
+        /// Require an attestation of the given type by one of the
+        /// keys, may be repeated; defaults to
+        /// `signify.requiredAttestations`
+        #[arg(long = "attestation", value_name = "TYPE")]
+        attestations: Vec<String>,
+
+        /// Verify the target of every ref matching the given glob
+        /// (e.g. `refs/tags/v*`), rather than a single revision
+        #[arg(long = "refs", value_name = "GLOB", conflicts_with = "git_rev")]
+        refs_glob: Option<String>,
 
         /// The signed git revision to verify
-        git_rev: String,
+        #[arg(required_unless_present = "refs_glob")]
+        git_rev: Option<String>,
     },
     /// Push signify data to a remote repository
     Push {
-        /// The name of the remote repository
-        remote: Option<Cow<'static, str>>,
+        #[command(flatten)]
+        filter: filter::FilterArgs,
+
+        /// The name of the remote repository, defaults
+        /// to `signify.defaultRemote` or `origin`
+        remote: Option<String>,
     },
     /// Pull signify data from a remote repository
     Pull {
-        /// The name of the remote repository
-        remote: Option<Cow<'static, str>>,
+        /// Fetch signatures into `refs/signify/remotes/<remote>`, and
+        /// only keep those which verify under a known key
+        #[arg(long)]
+        verify: bool,
+
+        /// The path to the base64 encoded public keys to verify with,
+        /// defaults to `signify.publicKeys`
+        #[arg(short = 'k', long, env = "GIT_KEY_PUB", requires = "verify")]
+        public_key: Option<PathBuf>,
+
+        #[command(flatten)]
+        filter: filter::FilterArgs,
+
+        /// The name of the remote repository, defaults
+        /// to `signify.defaultRemote` or `origin`
+        remote: Option<String>,
     },
     /// List signatures stored in this repository
-    ListSignatures {
-        /// Output JSON
-        #[arg(long)]
-        json: bool,
+    ListSignatures(list_signatures::ListArgs),
+    /// Audit all signatures stored in this repository
+    ///
+    /// Each signature must sign over the object named by its reference,
+    /// and verify under the key whose fingerprint is named by it.
+    Fsck {
+        /// The path to the base64 encoded public keys to verify with,
+        /// defaults to `signify.publicKeys`
+        #[arg(short = 'k', long, env = "GIT_KEY_PUB")]
+        public_key: Option<PathBuf>,
+    },
+    /// Report stale signatures, and optionally delete them
+    ///
+    /// Signatures are stale if they cannot be loaded, if they sign over
+    /// an object other than the one in their reference name, or if the
+    /// signed object is missing or unreachable from any reference.
+    Prune {
+        /// Delete the stale signatures
+        #[arg(short = 'd', long)]
+        delete: bool,
 
-        /// The name of the remote repository, to
-        /// list remote signatures
-        #[arg(short = 'R', long)]
+        /// The name of the remote repository, to delete the
+        /// stale signatures from, rather than locally. Only
+        /// signatures whose reference name is malformed or does
+        /// not match the signed object are deleted from it
+        #[arg(short = 'R', long, requires = "delete")]
         remote: Option<String>,
     },
+    /// Manage the append-only transparency log of signatures
+    ///
+    /// The reflog of the log is kept for audit: when it is created,
+    /// `gc.refs/signify/log.reflogExpire` and
+    /// `gc.refs/signify/log.reflogExpireUnreachable` are set to `never`
+    /// in the repository config, unless they are already set.
+    #[command(subcommand)]
+    Log(LogAction),
+    /// Show when signatures over a revision were added, replaced
+    /// or removed, and by whom
+    ///
+    /// The history is the reflog of `refs/signify/history`. When it is
+    /// created, `gc.refs/signify/history.reflogExpire` and
+    /// `gc.refs/signify/history.reflogExpireUnreachable` are set to
+    /// `never` in the repository config, unless they are already set,
+    /// such that `git gc` keeps it.
+    History {
+        /// The signed git revision
+        git_rev: String,
+    },
     /// Look-up a signature revision
     RevLookup {
-        /// Path to the base64 encoded public key that signed the rev
+        /// Path to the base64 encoded public key that signed the rev,
+        /// defaults to `signify.publicKeys`
         #[arg(short = 'k', long, env = "GIT_KEY_PUB")]
-        public_key: PathBuf,
+        public_key: Option<PathBuf>,
 
         /// Revision whose signature will be looked up
         git_rev: String,
     },
+    /// Emulate gpg, to be used as git's `gpg.program`
+    ///
+    /// Sign with the key at `user.signingKey` (or `GIT_KEY_SEC` and
+    /// `signify.secretKey`), and verify with the keys at `GIT_KEY_PUB`
+    /// (or `signify.publicKeys`).
+    GpgShim {
+        /// Arguments passed by git to `gpg.program`
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
     /// Generate shell completions
     ShellCompletions {
         /// The shell to generate completions for
@@ -98,18 +340,20 @@ enum Action {
 enum RawAction {
     /// Sign an arbitrary object and return a tree with the signature
     Sign {
-        /// The path to the base64 encoded secret key to sign with
+        /// The path to the base64 encoded secret key to sign with,
+        /// defaults to `signify.secretKey`
         #[arg(short = 'k', long, env = "GIT_KEY_SEC")]
-        secret_key: PathBuf,
+        secret_key: Option<PathBuf>,
 
         /// The git revision to sign
         git_rev: String,
     },
     /// Verify the signature contained in a tree object
     Verify {
-        /// The path to the base64 encoded public key to verify with
+        /// The path to the base64 encoded public key to verify with,
+        /// defaults to `signify.publicKeys`
         #[arg(short = 'k', long, env = "GIT_KEY_PUB")]
-        public_key: PathBuf,
+        public_key: Option<PathBuf>,
 
         /// Print the id of the signed object to stdout
         #[arg(short = 'p', long)]
@@ -123,55 +367,384 @@ enum RawAction {
 #[derive(Subcommand)]
 enum RmAction {
     /// Remove git-signify signatures
+    ///
+    /// By default, the signatures made by the given keys over the given
+    /// revision are removed.
     Signature {
-        /// The path to the base64 encoded public key of the signer
+        /// The path to the base64 encoded public key of the signer,
+        /// defaults to `signify.publicKeys`
         #[arg(short = 'k', long, env = "GIT_KEY_PUB")]
-        public_key: PathBuf,
+        public_key: Option<PathBuf>,
 
         /// The name of the remote repository, in case
         /// we wish to remove a remote signature
         #[arg(short = 'R', long)]
         remote: Option<String>,
 
+        /// Remove the signatures made by all keys over the revision
+        #[arg(long, conflicts_with_all = ["all_revs", "glob"])]
+        all_keys: bool,
+
+        /// Remove the signatures made by the keys over all revisions
+        #[arg(long, conflicts_with_all = ["git_rev", "glob"])]
+        all_revs: bool,
+
+        /// Remove all references under `refs/signify/` matching
+        /// the given glob
+        #[arg(long, conflicts_with = "git_rev")]
+        glob: Option<String>,
+
         /// The git revision whose signature we wish to remove
-        git_rev: String,
+        #[arg(required_unless_present_any = ["all_revs", "glob"])]
+        git_rev: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeyAction {
+    /// Convert a key between the signify and minisign formats
+    ///
+    /// Encrypted secret keys remain encrypted with the same passphrase.
+    Convert {
+        /// Convert a secret key, rather than a public key
+        #[arg(short = 's', long)]
+        secret: bool,
+
+        /// The format to convert the key to
+        #[arg(short = 't', long, value_enum)]
+        to: key::convert::KeyFormat,
+
+        /// Overwrite the output file, if it already exists
+        #[arg(short = 'f', long)]
+        force: bool,
+
+        /// The path to the base64 encoded key to convert
+        key: PathBuf,
+
+        /// The path to write the converted key to
+        output: PathBuf,
+    },
+    /// Change the passphrase of a secret key
+    ///
+    /// The key is decrypted with its current passphrase, and written
+    /// back encrypted with the new one. An empty passphrase removes
+    /// the encryption of signify and minisign keys.
+    Passwd {
+        /// The path to the base64 encoded secret key to re-encrypt,
+        /// defaults to `signify.secretKey`
+        #[arg(short = 'k', long, env = "GIT_KEY_SEC")]
+        secret_key: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum LogAction {
+    /// Append signatures to the log, unless they already appear in it
+    ///
+    /// Signatures are appended automatically as they are added if
+    /// `signify.transparencyLog` is set.
+    Append {
+        /// The signature refs to append, defaults to all of them
+        refnames: Vec<String>,
+    },
+    /// Check that the log is a consistent, append-only series of
+    /// entries over the objects they name
+    Verify,
+    /// List the signatures which never appeared in the log, and fail
+    /// if there are any
+    Missing,
+}
+
+#[derive(Subcommand)]
+enum HookAction {
+    /// Push signatures along with the objects they sign
+    ///
+    /// Reads the ref updates of a `pre-push` hook, and pushes the
+    /// local signature refs over the pushed objects to the same remote.
+    PrePush {
+        /// Block pushes of refs matching `signify.protectedRefs`
+        /// (defaults to `refs/tags/v*`) to unsigned objects
+        #[arg(long)]
+        block_unsigned: bool,
+
+        /// The name of the remote being pushed to
+        remote: String,
+
+        /// The url of the remote being pushed to
+        url: Option<String>,
+    },
+    /// Reject pushes of protected refs to unsigned objects
+    ///
+    /// Reads the `<old> <new> <ref>` lines of a `pre-receive` hook.
+    /// Refs matching `signify.protectedRefs` (defaults to `refs/tags/v*`)
+    /// must point at objects signed by keys in the keyring, either in
+    /// the same push or beforehand, and attested as required by
+    /// `signify.requiredAttestations`.
+    PreReceive {
+        /// The path to the base64 encoded public keys allowed to
+        /// sign, defaults to `signify.publicKeys`
+        #[arg(short = 'k', long, env = "GIT_KEY_PUB")]
+        public_key: Option<PathBuf>,
+
+        /// Minimum number of keys whose signature must verify,
+        /// defaults to `signify.threshold` or 1
+        #[arg(short = 't', long)]
+        threshold: Option<usize>,
+
+        /// Require an attestation of the given type by one of the
+        /// keys, may be repeated; defaults to
+        /// `signify.requiredAttestations`
+        #[arg(long = "attestation", value_name = "TYPE")]
+        attestations: Vec<String>,
+    },
+    /// Verify the checked-out or fetched revisions
+    ///
+    /// Verifies `HEAD`, or the tags fetched by the last `git fetch`,
+    /// against the signatures in the repository. Merge commits are
+    /// verified through the tips they merged in. Suitable for
+    /// `post-checkout` and `post-merge` hooks, or fetch wrappers.
+    /// Keyrings in the work tree are refused, since the verified
+    /// revisions may have changed them.
+    Verify {
+        /// The path to the base64 encoded public keys allowed to
+        /// sign, defaults to `signify.publicKeys`
+        #[arg(short = 'k', long, env = "GIT_KEY_PUB")]
+        public_key: Option<PathBuf>,
+
+        /// Minimum number of keys whose signature must verify,
+        /// defaults to `signify.threshold` or 1
+        #[arg(short = 't', long)]
+        threshold: Option<usize>,
+
+        /// Require an attestation of the given type by one of the
+        /// keys, may be repeated; defaults to
+        /// `signify.requiredAttestations`
+        #[arg(long = "attestation", value_name = "TYPE")]
+        attestations: Vec<String>,
+
+        /// Verify the tags listed in `FETCH_HEAD`, rather than `HEAD`
+        #[arg(long)]
+        fetched: bool,
+
+        /// Fail when revisions are unverified, rather than warning
+        #[arg(long)]
+        fail: bool,
+
+        /// Arguments passed by git to the hook, which are ignored
+        #[arg(hide = true)]
+        hook_args: Vec<String>,
     },
 }
 
+impl Action {
+    /// Whether this action supports [`output::Format::Json`].
+    const fn supports_json(&self) -> bool {
+        matches!(
+            self,
+            Self::Raw(_)
+                | Self::Fingerprint { .. }
+                | Self::Sign { .. }
+                | Self::Countersign { .. }
+                | Self::Attest { .. }
+                | Self::Tag { .. }
+                | Self::Verify { .. }
+                | Self::ListSignatures(_)
+                | Self::History { .. }
+                | Self::RevLookup { .. }
+        )
+    }
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    // scripts would otherwise silently get human readable output
+    if args.format.is_json() && !args.action.supports_json() {
+        anyhow::bail!("This command does not support --format json");
+    }
+
+    // these must be honored before the repository is opened,
+    // including the one holding the config
+    for directory in &args.directories {
+        env::set_current_dir(directory)
+            .with_context(|| format!("Cannot change to {}", directory.display()))?;
+    }
+    if let Some(git_dir) = &args.git_dir {
+        env::set_var("GIT_DIR", git_dir);
+    }
+
+    let config = config::Config::open()?;
+
     match args.action {
         Action::Raw(RawAction::Sign {
             secret_key,
             git_rev: rev,
-        }) => raw::sign::command(secret_key, rev),
+        }) => raw::sign::command(config.secret_key(secret_key)?, rev, args.format),
         Action::Raw(RawAction::Verify {
             public_key,
             print_signed_oid: recover,
             git_tree: rev,
-        }) => raw::verify::command(public_key, recover, rev),
+        }) => raw::verify::command(config.public_keys(public_key)?, recover, rev, args.format),
         Action::Rm(RmAction::Signature {
             public_key,
+            remote,
+            all_keys,
+            all_revs,
+            glob,
             git_rev,
+        }) => {
+            let target = match (glob, git_rev) {
+                (Some(glob), _) => rm::signature::Target::Glob(glob),
+                (None, _) if all_revs => {
+                    rm::signature::Target::Key(config.public_keys(public_key)?)
+                }
+                (None, Some(rev)) if all_keys => rm::signature::Target::Revision(rev),
+                (None, Some(rev)) => rm::signature::Target::Signature {
+                    key_path: config.public_keys(public_key)?,
+                    rev,
+                },
+                (None, None) => anyhow::bail!("No git revision provided"),
+            };
+            rm::signature::command(target, remote)
+        }
+        Action::Key(KeyAction::Convert {
+            secret,
+            to,
+            force,
+            key,
+            output,
+        }) => key::convert::command(secret, to, force, key, output),
+        Action::Key(KeyAction::Passwd { secret_key }) => {
+            key::passwd::command(config.secret_key(secret_key)?)
+        }
+        Action::Hook(HookAction::PrePush {
+            block_unsigned,
             remote,
-        }) => rm::signature::command(public_key, git_rev, remote),
-        Action::Fingerprint { key } => fingerprint::command(key),
+            url,
+        }) => hook::pre_push::command(remote, url, config.protected_refs()?, block_unsigned),
+        Action::InstallHooks {
+            hooks,
+            block_unsigned,
+            force,
+        } => install_hooks::command(hooks, block_unsigned, force, config.hooks_path()?),
+        Action::Hook(HookAction::Verify {
+            public_key,
+            threshold,
+            attestations,
+            fetched,
+            fail,
+            hook_args: _,
+        }) => hook::verify::command(
+            config.public_keys(public_key)?,
+            config.threshold(threshold)?,
+            config.required_attestations(attestations)?,
+            fetched,
+            fail,
+        ),
+        Action::Hook(HookAction::PreReceive {
+            public_key,
+            threshold,
+            attestations,
+        }) => hook::pre_receive::command(
+            config.public_keys(public_key)?,
+            config.protected_refs()?,
+            config.threshold(threshold)?,
+            config.required_attestations(attestations)?,
+        ),
+        Action::Fingerprint { key } => fingerprint::command(config.public_keys(key)?, args.format),
         Action::Sign {
             secret_key,
+            refs_glob,
+            force,
+            git_rev: rev,
+        } => sign::command(
+            config.secret_key(secret_key)?,
+            rev,
+            refs_glob,
+            force,
+            args.format,
+        ),
+        Action::Countersign {
+            secret_key,
+            fingerprints,
+            signers,
             git_rev: rev,
-        } => sign::command(secret_key, rev),
+        } => countersign::command(
+            config.secret_key(secret_key)?,
+            rev,
+            filter::resolve_signers(&fingerprints, signers)?,
+            args.format,
+        ),
+        Action::Attest {
+            secret_key,
+            kind,
+            payload_path,
+            force,
+            git_rev: rev,
+        } => attest::command(
+            config.secret_key(secret_key)?,
+            kind,
+            payload_path,
+            force,
+            rev,
+            args.format,
+        ),
+        Action::Tag {
+            secret_key,
+            message,
+            force,
+            push,
+            remote,
+            name,
+            git_rev: rev,
+        } => tag::command(
+            config.secret_key(secret_key)?,
+            name,
+            rev,
+            message,
+            force,
+            push.then(|| config.default_remote(remote)).transpose()?,
+            args.format,
+        ),
         Action::Verify {
             public_key,
+            threshold,
+            witness_path,
+            attestations,
+            refs_glob,
             git_rev: rev,
-        } => verify::command(public_key, rev),
-        Action::Push { remote } => push::command(&remote.unwrap_or(Cow::Borrowed("origin"))),
-        Action::Pull { remote } => pull::command(&remote.unwrap_or(Cow::Borrowed("origin"))),
-        Action::ListSignatures { json, remote } => list_signatures::command(json, remote),
+        } => verify::command(
+            config.public_keys(public_key)?,
+            config.threshold(threshold)?,
+            witness_path,
+            config.required_attestations(attestations)?,
+            rev,
+            refs_glob,
+            args.format,
+        ),
+        Action::Push { filter, remote } => push::command(&config.default_remote(remote)?, filter),
+        Action::Pull {
+            verify,
+            public_key,
+            filter,
+            remote,
+        } => pull::command(
+            &config.default_remote(remote)?,
+            verify.then(|| config.public_keys(public_key)).transpose()?,
+            filter,
+        ),
+        Action::ListSignatures(list_args) => list_signatures::command(args.format, list_args),
+        Action::Log(LogAction::Append { refnames }) => log::append::command(refnames),
+        Action::Log(LogAction::Verify) => log::verify::command(),
+        Action::Log(LogAction::Missing) => log::missing::command(),
+        Action::History { git_rev: rev } => history::command(rev, args.format),
+        Action::Fsck { public_key } => fsck::command(config.public_keys(public_key)?),
+        Action::Prune { delete, remote } => prune::command(delete, remote),
         Action::RevLookup {
             public_key,
             git_rev: rev,
-        } => rev_lookup::command(public_key, rev),
+        } => rev_lookup::command(config.public_keys(public_key)?, rev, args.format),
+        Action::GpgShim { args } => gpg_shim::command(&config, args),
         Action::ShellCompletions { shell } => shell_completions::command(shell),
     }
 }
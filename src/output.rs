@@ -0,0 +1,135 @@
+//! Render the results of commands in a machine readable form.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use git2::{Oid, Repository};
+use serde::Serialize;
+
+/// Version of the schema of the JSON output, bumped whenever
+/// backwards incompatible changes are made to it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Output format of commands.
+#[derive(Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human readable output
+    #[default]
+    Human,
+    /// JSON output, with a versioned schema
+    Json,
+}
+
+impl Format {
+    /// Whether this is [`Format::Json`].
+    pub const fn is_json(self) -> bool {
+        matches!(self, Self::Json)
+    }
+}
+
+/// Top-level JSON document printed by commands.
+#[derive(Serialize)]
+struct Document<'result, T> {
+    /// Version of the schema of this document.
+    schema_version: u32,
+    /// The command that produced this document.
+    command: &'static str,
+    /// The results of the command.
+    result: &'result T,
+}
+
+/// Print the `result` of `command` as a JSON document.
+pub fn print_json<T: Serialize>(command: &'static str, result: &T) -> Result<()> {
+    let document = serde_json::to_string(&Document {
+        schema_version: SCHEMA_VERSION,
+        command,
+        result,
+    })
+    .context("Failed to serialize JSON output")?;
+    println!("{document}");
+    Ok(())
+}
+
+/// Git object, as described in JSON output.
+#[derive(Serialize)]
+pub struct ObjectInfo {
+    /// The id of the object.
+    pub oid: String,
+    /// The type of the object, if it is present in the repository.
+    #[serde(rename = "type")]
+    pub kind: Option<&'static str>,
+}
+
+impl ObjectInfo {
+    /// Describe the object `oid` of `repo`.
+    pub fn new(repo: &Repository, oid: Oid) -> Self {
+        let kind = repo
+            .find_object(oid, None)
+            .ok()
+            .and_then(|object| object.kind())
+            .map(|kind| kind.str());
+        Self {
+            oid: oid.to_string(),
+            kind,
+        }
+    }
+}
+
+/// Key, as described in JSON output.
+#[derive(Serialize)]
+pub struct KeyInfo {
+    /// The path the key was read from.
+    pub key_path: PathBuf,
+    /// The fingerprint of the key.
+    pub fingerprint: String,
+    /// The algorithm of the key.
+    pub algorithm: &'static str,
+}
+
+impl KeyInfo {
+    /// Describe the public key read from `key_path`.
+    pub fn new(key_path: PathBuf, public_key: &crate::utils::PublicKey) -> Result<Self> {
+        Ok(Self {
+            key_path,
+            fingerprint: public_key.fingerprint()?.to_string(),
+            algorithm: public_key.algorithm().as_str(),
+        })
+    }
+}
+
+/// Outcome of verifying a signature, as described in JSON output.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    /// The signature verified successfully.
+    Valid,
+    /// No signature was found.
+    Missing,
+    /// The signature failed to verify.
+    Invalid,
+}
+
+/// Print `rows` as a table, with columns aligned under `header`.
+pub fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let print_row = |cells: [&str; N]| {
+        let line: Vec<_> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    print_row(header);
+    for row in rows {
+        print_row(row.each_ref().map(String::as_str));
+    }
+}
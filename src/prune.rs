@@ -0,0 +1,331 @@
+//! Find and remove stale signature references.
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use git2::{ObjectType, Oid, Reference, Repository};
+
+use crate::history::{self, Event};
+use crate::remote;
+use crate::utils::{self, TreeSignature};
+
+/// Reason why a signature reference is considered stale.
+enum Problem {
+    /// The reference name does not follow `<fingerprint>/<oid>`.
+    MalformedName,
+    /// The signature could not be loaded.
+    Unloadable(anyhow::Error),
+    /// The signed object does not exist in the repository.
+    MissingObject(Oid),
+    /// The signed object is not reachable from any reference,
+    /// e.g. after a rebase or the deletion of a branch.
+    UnreachableObject(Oid),
+    /// The signed object does not match the reference name.
+    Mismatch {
+        /// Object id in the reference name.
+        expected: Oid,
+        /// Object id actually signed over.
+        found: Oid,
+    },
+}
+
+impl Problem {
+    /// Whether the signature is stale wherever it is stored. Other
+    /// problems may only affect the local repository, e.g. objects which
+    /// were never fetched, or signatures in formats it cannot load, so
+    /// they do not warrant deleting signatures from a remote.
+    const fn is_conclusive(&self) -> bool {
+        matches!(self, Self::MalformedName | Self::Mismatch { .. })
+    }
+
+    /// Describe the problem.
+    fn reason(&self) -> String {
+        match self {
+            Self::MalformedName => "malformed reference name".to_owned(),
+            Self::Unloadable(e) => format!("failed to load signature: {e:#}"),
+            Self::MissingObject(oid) => format!("signed object {oid} is missing"),
+            Self::UnreachableObject(oid) => format!("signed object {oid} is unreachable"),
+            Self::Mismatch { expected, found } => {
+                format!("signature is over {found}, rather than {expected}")
+            }
+        }
+    }
+}
+
+/// Names of stale signature references, along with their problem.
+type StaleRefs = Vec<(String, Problem)>;
+
+/// Signature references to check, along with the objects reachable
+/// from the other references.
+struct Signatures {
+    /// Names of the signature references, and the signatures they
+    /// point at.
+    refs: Vec<(String, Oid)>,
+    /// Objects reachable from the other references, unless unknown.
+    reachable: Option<HashSet<Oid>>,
+}
+
+/// Execute the `prune` command. If a `remote` is given, the signatures
+/// it advertises are checked against the references it advertises, and
+/// those whose [problem](Problem::is_conclusive) does not depend on the
+/// local repository are deleted from it.
+pub fn command(delete: bool, remote: Option<String>) -> Result<()> {
+    let repo = utils::open_repository()?;
+
+    let Signatures { refs, reachable } = match &remote {
+        Some(remote) => remote_signatures(&repo, remote)?,
+        None => local_signatures(&repo)?,
+    };
+    if reachable.is_none() {
+        println!("Some refs of the remote are missing locally, only checking that signatures load");
+    }
+
+    let mut stale = Vec::new();
+    for (refname, signature_oid) in refs {
+        if let Some(problem) = check_reference(&repo, reachable.as_ref(), &refname, signature_oid) {
+            stale.push((refname, problem));
+        }
+    }
+    let (stale, kept) = partition_deletable(stale, remote.is_some());
+
+    if !kept.is_empty() {
+        println!("Possibly stale signatures, kept on the remote:");
+        for (refname, problem) in &kept {
+            println!("  - {refname} ({})", problem.reason());
+        }
+    }
+
+    if stale.is_empty() {
+        println!("No stale signatures found");
+        return Ok(());
+    }
+
+    println!("Stale signatures:");
+    for (refname, problem) in &stale {
+        println!("  - {refname} ({})", problem.reason());
+    }
+
+    if !delete {
+        return Ok(());
+    }
+
+    let refnames: Vec<_> = stale.into_iter().map(|(refname, _)| refname).collect();
+
+    if let Some(remote) = remote {
+        remote::delete(&repo, &remote, &refnames).context("Failed to remove remote signatures")?;
+        println!("Deleted from remote {remote}");
+    } else {
+        for refname in &refnames {
+            let mut reference = repo
+                .find_reference(refname)
+                .with_context(|| format!("Failed to look-up reference {refname}"))?;
+            if let Some(signature_oid) = reference.target() {
+                history::record(&repo, "prune", Event::Removed, refname, signature_oid)?;
+            }
+            reference
+                .delete()
+                .with_context(|| format!("Failed to delete reference {refname}"))?;
+        }
+        println!("Deleted local references");
+    }
+
+    Ok(())
+}
+
+/// Split the `stale` signatures into those to delete, and those to keep
+/// as their problem may only affect the local repository, if they are
+/// stored on a `remote`.
+fn partition_deletable(stale: StaleRefs, remote: bool) -> (StaleRefs, StaleRefs) {
+    stale
+        .into_iter()
+        .partition(|(_, problem)| !remote || problem.is_conclusive())
+}
+
+/// Collect the local signature references, along with the objects
+/// reachable from the other local references.
+fn local_signatures(repo: &Repository) -> Result<Signatures> {
+    let mut signatures = Vec::new();
+    for maybe_ref in repo
+        .references_glob(utils::ALL_SIGNIFY_SIGNATURE_REFS)
+        .context("Failed to look-up signature refs")?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        let refname = reference.name().context("Invalid reference name")?;
+        if let Some(signature_oid) = reference.target() {
+            signatures.push((refname.to_owned(), signature_oid));
+        }
+    }
+
+    let mut roots = Vec::new();
+    if let Ok(head) = repo.head() {
+        roots.extend(head.target());
+    }
+    for maybe_ref in repo
+        .references()
+        .context("Failed to look-up git references")?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        if reference
+            .name()
+            .is_some_and(|refname| !refname.starts_with("refs/signify/"))
+        {
+            roots.extend(reference.target());
+        }
+    }
+
+    Ok(Signatures {
+        refs: signatures,
+        reachable: Some(reachable_objects(repo, &roots)?),
+    })
+}
+
+/// Collect the signature references advertised by `remote`, fetching
+/// the signatures they point at, along with the objects reachable from
+/// the other references it advertises. Reachability is unknown if some
+/// of these references are missing locally.
+fn remote_signatures(repo: &Repository, remote: &str) -> Result<Signatures> {
+    let remote_refs = remote::list(repo, remote)?;
+
+    // the signatures are only fetched to be loaded, under the namespace
+    // of unverified signatures
+    let remote_prefix = utils::remote_signature_refs_prefix(remote);
+    anyhow::ensure!(
+        Reference::is_valid_name(&format!("{remote_prefix}0")),
+        "Pruning remote signatures requires a named remote, got {remote}"
+    );
+    remote::fetch(
+        repo,
+        remote,
+        &[format!(
+            "+{}*:{remote_prefix}*",
+            utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX
+        )],
+        true,
+    )?;
+
+    let mut signatures = Vec::new();
+    let mut roots = Vec::new();
+    for remote_ref in remote_refs {
+        if remote_ref
+            .name
+            .starts_with(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+        {
+            signatures.push((remote_ref.name, remote_ref.oid));
+        } else if !remote_ref.name.starts_with("refs/signify/") {
+            roots.push(remote_ref.oid);
+        }
+    }
+
+    let reachable = roots
+        .iter()
+        .all(|&oid| repo.find_object(oid, None).is_ok())
+        .then(|| reachable_objects(repo, &roots))
+        .transpose()?;
+
+    Ok(Signatures {
+        refs: signatures,
+        reachable,
+    })
+}
+
+/// Check the signature referenced by `refname` for problems. Missing
+/// and unreachable objects are only reported if `reachable` is known.
+fn check_reference(
+    repo: &Repository,
+    reachable: Option<&HashSet<Oid>>,
+    refname: &str,
+    signature_oid: Oid,
+) -> Option<Problem> {
+    let Some((_, expected)) = refname
+        .strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+        .and_then(utils::parse_signer_and_oid)
+    else {
+        return Some(Problem::MalformedName);
+    };
+
+    let found = match TreeSignature::load_oid(repo, signature_oid)
+        .and_then(|tree_sig| tree_sig.dereference())
+    {
+        Ok(found) => found,
+        Err(e) => return Some(Problem::Unloadable(e)),
+    };
+
+    if found != expected {
+        return Some(Problem::Mismatch { expected, found });
+    }
+
+    let reachable = reachable?;
+    let Ok(object) = repo.find_object(found, None) else {
+        return Some(Problem::MissingObject(found));
+    };
+
+    // only commits and tags can be orphaned by history rewrites
+    match object.kind() {
+        Some(ObjectType::Commit | ObjectType::Tag) if !reachable.contains(&found) => {
+            Some(Problem::UnreachableObject(found))
+        }
+        _ => None,
+    }
+}
+
+/// Collect the commits reachable from `roots`, along with the annotated
+/// tags and other objects the roots point to.
+fn reachable_objects(repo: &Repository, roots: &[Oid]) -> Result<HashSet<Oid>> {
+    let mut reachable: HashSet<_> = roots.iter().copied().collect();
+    let mut walk = repo.revwalk().context("Failed to walk revisions")?;
+
+    for &root in roots {
+        if let Ok(commit) = repo
+            .find_object(root, None)
+            .and_then(|object| object.peel_to_commit())
+        {
+            walk.push(commit.id()).context("Failed to walk revisions")?;
+        }
+    }
+
+    for maybe_oid in walk {
+        reachable.insert(maybe_oid.context("Failed to walk revisions")?);
+    }
+
+    Ok(reachable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_deletion_skips_local_problems() {
+        let oid = Oid::from_str(&"a".repeat(40)).unwrap();
+        let other = Oid::from_str(&"b".repeat(40)).unwrap();
+        let stale = || {
+            vec![
+                ("malformed".to_owned(), Problem::MalformedName),
+                (
+                    "unloadable".to_owned(),
+                    Problem::Unloadable(anyhow::anyhow!("unknown version")),
+                ),
+                ("missing".to_owned(), Problem::MissingObject(oid)),
+                ("unreachable".to_owned(), Problem::UnreachableObject(oid)),
+                (
+                    "mismatch".to_owned(),
+                    Problem::Mismatch {
+                        expected: oid,
+                        found: other,
+                    },
+                ),
+            ]
+        };
+        let names = |refs: &[(String, Problem)]| -> Vec<String> {
+            refs.iter().map(|(refname, _)| refname.clone()).collect()
+        };
+
+        let (deleted, kept) = partition_deletable(stale(), true);
+        assert_eq!(names(&deleted), ["malformed", "mismatch"]);
+        assert_eq!(names(&kept), ["unloadable", "missing", "unreachable"]);
+
+        let (deleted, kept) = partition_deletable(stale(), false);
+        assert_eq!(deleted.len(), 5);
+        assert!(kept.is_empty());
+    }
+}
@@ -1,24 +1,267 @@
 //! Pull data from a remote repo.
 
-use std::process::Command;
-
-use anyhow::{anyhow, Context, Result};
-
-use crate::utils::ALL_SIGNIFY_REFS;
-
-/// Execute the `pull` command.
-pub fn command(remote: &str) -> Result<()> {
-    let exit_code = Command::new("git")
-        .arg("fetch")
-        .arg(remote)
-        .arg(format!("{ALL_SIGNIFY_REFS}:{ALL_SIGNIFY_REFS}"))
-        .spawn()
-        .context("Failed to spawn git command")?
-        .wait()
-        .context("Failed to wait for git command")?;
-    if exit_code.success() {
-        Ok(())
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum as _;
+use git2::{Oid, Reference, Repository};
+
+use crate::filter::{FilterArgs, Namespace, RefFilter};
+use crate::history::{self, Event};
+use crate::log::entry::LOG_REF;
+use crate::remote;
+use crate::utils::{self, Keyring, SignatureStatus};
+
+/// Execute the `pull` command. If a keyring is provided, signatures
+/// are fetched into `refs/signify/remotes/<remote>` instead, and only
+/// promoted to the local signature namespace once verified.
+pub fn command(remote: &str, keyring: Option<PathBuf>, filter: FilterArgs) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let dry_run = filter.dry_run;
+    let filter = filter.into_filter(&repo)?;
+
+    let Some(keyring) = keyring else {
+        let refspecs: Vec<_> = if filter.is_empty() {
+            // every namespace exchanged with remotes, but not the local
+            // signature history, nor signatures fetched from remotes
+            Namespace::value_variants()
+                .iter()
+                .map(|namespace| match namespace {
+                    Namespace::Log => fetch_refspec(LOG_REF),
+                    _ => fetch_refspec(&format!("{}*", namespace.prefix())),
+                })
+                .collect()
+        } else {
+            selected_remote_refs(&repo, remote, &filter)?
+                .iter()
+                .map(|refname| fetch_refspec(refname))
+                .collect()
+        };
+        if dry_run {
+            remote::print_refspecs(&refspecs);
+            return Ok(());
+        }
+        if refspecs.is_empty() {
+            return Ok(());
+        }
+        let before = signature_refs(&repo)?;
+        remote::fetch(&repo, remote, &refspecs, false)?;
+        if refspecs.iter().any(|refspec| refspec == LOG_REF) {
+            fast_forward_log(&repo)?;
+        }
+        return record_fetched_signatures(&repo, &before);
+    };
+
+    let remote_prefix = utils::remote_signature_refs_prefix(remote);
+
+    anyhow::ensure!(
+        Reference::is_valid_name(&format!("{remote_prefix}0")),
+        "Verified pulls require a named remote, got {remote}"
+    );
+
+    let (refspecs, prune) = if filter.is_empty() {
+        let refspecs = vec![format!(
+            "+{}*:{remote_prefix}*",
+            utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX
+        )];
+        (refspecs, true)
     } else {
-        Err(anyhow!("Exit code of git: {exit_code}"))
+        let refspecs = selected_remote_refs(&repo, remote, &filter)?
+            .into_iter()
+            .filter_map(|refname| {
+                let signer_and_oid =
+                    refname.strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)?;
+                Some(format!("+{refname}:{remote_prefix}{signer_and_oid}"))
+            })
+            .collect();
+        (refspecs, false)
+    };
+
+    if dry_run {
+        remote::print_refspecs(&refspecs);
+        return Ok(());
+    }
+
+    let keyring = Keyring::open(keyring)?;
+    remote::fetch(&repo, remote, &refspecs, prune)?;
+
+    promote_verified_signatures(&repo, &keyring, &remote_prefix)
+}
+
+/// Build the refspec fetching `refname`, or a glob, into the local
+/// reference with the same name. The transparency log is only fetched
+/// into `FETCH_HEAD`, to then be fast-forwarded by [`fast_forward_log`].
+fn fetch_refspec(refname: &str) -> String {
+    if refname == LOG_REF {
+        refname.to_owned()
+    } else {
+        format!("{refname}:{refname}")
+    }
+}
+
+/// Fast-forward the local transparency log to the one fetched into
+/// `FETCH_HEAD`, refusing logs which were rewritten by the remote.
+fn fast_forward_log(repo: &Repository) -> Result<()> {
+    let mut fetched = None;
+    repo.fetchhead_foreach(|refname, _, &oid, _| {
+        if refname == LOG_REF {
+            fetched = Some(oid);
+        }
+        true
+    })
+    .context("Failed to read FETCH_HEAD")?;
+    let Some(fetched) = fetched else {
+        return Ok(());
+    };
+
+    let message = "git-signify pull: fast-forward log";
+    match repo.refname_to_id(LOG_REF) {
+        Ok(local) if local == fetched => return Ok(()),
+        Ok(local) => {
+            anyhow::ensure!(
+                repo.graph_descendant_of(fetched, local)
+                    .context("Failed to compare the fetched log to the local one")?,
+                "Refusing to pull the transparency log: the remote log {fetched} does not \
+                 extend the local log {local}"
+            );
+            utils::keep_reflog(repo, LOG_REF)?;
+            repo.reference_matching(LOG_REF, fetched, true, local, message)
+        }
+        Err(_) => {
+            utils::keep_reflog(repo, LOG_REF)?;
+            repo.reference(LOG_REF, fetched, false, message)
+        }
+    }
+    .context("Failed to update the transparency log")?;
+
+    Ok(())
+}
+
+/// Map the local signature references to the signatures they point at.
+fn signature_refs(repo: &Repository) -> Result<HashMap<String, Oid>> {
+    let mut refs = HashMap::new();
+    for maybe_ref in repo
+        .references_glob(utils::ALL_SIGNIFY_SIGNATURE_REFS)
+        .context("Failed to look-up signature refs")?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        if let (Some(refname), Some(signature_oid)) = (reference.name(), reference.target()) {
+            refs.insert(refname.to_owned(), signature_oid);
+        }
+    }
+    Ok(refs)
+}
+
+/// Record the signatures added or replaced by a fetch in the history,
+/// given the signature references from `before` the fetch.
+fn record_fetched_signatures(repo: &Repository, before: &HashMap<String, Oid>) -> Result<()> {
+    let mut after: Vec<_> = signature_refs(repo)?.into_iter().collect();
+    after.sort();
+    for (refname, signature_oid) in after {
+        let event = match before.get(&refname) {
+            None => Event::Added,
+            Some(&previous) if previous != signature_oid => Event::Replaced,
+            Some(_) => continue,
+        };
+        history::record(repo, "pull", event, &refname, signature_oid)?;
     }
+    Ok(())
+}
+
+/// List the signify references advertised by `remote` which
+/// are selected by `filter`.
+fn selected_remote_refs(
+    repo: &Repository,
+    remote: &str,
+    filter: &RefFilter,
+) -> Result<Vec<String>> {
+    let refs = remote::list(repo, remote)?
+        .into_iter()
+        .map(|remote_ref| remote_ref.name)
+        .filter(|refname| filter.matches(refname))
+        .collect();
+    Ok(refs)
+}
+
+/// Promote the valid signatures under `remote_prefix` to the local
+/// signature namespace, and report the remaining ones.
+fn promote_verified_signatures(
+    repo: &Repository,
+    keyring: &Keyring,
+    remote_prefix: &str,
+) -> Result<()> {
+    let mut promoted = Vec::new();
+    let mut unknown = Vec::new();
+    let mut conflicting = Vec::new();
+    let mut invalid = Vec::new();
+
+    for maybe_ref in repo
+        .references_glob(&format!("{remote_prefix}*"))
+        .context("Failed to look-up fetched signature refs")?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        let refname = reference.name().context("Invalid reference name")?;
+        let signature_oid = reference
+            .target()
+            .context("Fetched signature ref is not a direct reference")?;
+
+        let Some((signer, signed_oid)) = refname
+            .strip_prefix(remote_prefix)
+            .and_then(utils::parse_signer_and_oid)
+        else {
+            invalid.push(format!("{refname} (malformed reference name)"));
+            continue;
+        };
+
+        match utils::check_signature(repo, keyring, signer, signed_oid, signature_oid) {
+            SignatureStatus::Valid { key_path } => {
+                let local_ref = utils::craft_signature_reference(signer, signed_oid);
+
+                match repo.refname_to_id(&local_ref) {
+                    Ok(local_oid) if local_oid == signature_oid => {}
+                    Ok(_) => conflicting.push(local_ref),
+                    Err(_) => {
+                        repo.reference(
+                            &local_ref,
+                            signature_oid,
+                            false,
+                            "git-signify: promote verified signature",
+                        )
+                        .context("Failed to store reference to verified signature")?;
+                        history::record(repo, "pull", Event::Added, &local_ref, signature_oid)?;
+                        promoted.push(format!("{local_ref} ({})", key_path.display()));
+                    }
+                }
+            }
+            SignatureStatus::UnknownKey => unknown.push(refname.to_owned()),
+            SignatureStatus::Invalid(e) => invalid.push(format!("{refname} ({e:#})")),
+        }
+    }
+
+    for (title, refs) in [
+        ("Promoted verified signatures:", &promoted),
+        ("Signatures by unknown keys, not promoted:", &unknown),
+        (
+            "Signatures conflicting with local ones, not promoted:",
+            &conflicting,
+        ),
+        ("Invalid signatures, not promoted:", &invalid),
+    ] {
+        if refs.is_empty() {
+            continue;
+        }
+        println!("{title}");
+        for reference in refs {
+            println!("  - {reference}");
+        }
+    }
+
+    anyhow::ensure!(
+        invalid.is_empty(),
+        "Found {} invalid signatures",
+        invalid.len()
+    );
+
+    Ok(())
 }
@@ -1,24 +1,31 @@
 //! Push data to a remote repo.
 
-use std::process::Command;
+use anyhow::Result;
 
-use anyhow::{anyhow, Context, Result};
-
-use crate::utils::ALL_SIGNIFY_REFS;
+use crate::filter::FilterArgs;
+use crate::remote;
+use crate::utils::{self, ALL_SIGNIFY_REFS};
 
 /// Execute the `push` command.
-pub fn command(remote: &str) -> Result<()> {
-    let exit_code = Command::new("git")
-        .arg("push")
-        .arg(remote)
-        .arg(ALL_SIGNIFY_REFS)
-        .spawn()
-        .context("Failed to spawn git command")?
-        .wait()
-        .context("Failed to wait for git command")?;
-    if exit_code.success() {
-        Ok(())
-    } else {
-        Err(anyhow!("Exit code of git: {exit_code}"))
+pub fn command(remote: &str, filter: FilterArgs) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let dry_run = filter.dry_run;
+    let filter = filter.into_filter(&repo)?;
+
+    let refspecs: Vec<_> = remote::local_refspecs(&repo, ALL_SIGNIFY_REFS)?
+        .into_iter()
+        // signatures fetched from remotes have not been verified,
+        // and are therefore never matched by filters
+        .filter(|refspec| {
+            let (refname, _) = refspec.split_once(':').unwrap_or((refspec, ""));
+            filter.matches(refname)
+        })
+        .collect();
+
+    if dry_run {
+        remote::print_refspecs(&refspecs);
+        return Ok(());
     }
+
+    remote::push(&repo, remote, &refspecs)
 }
@@ -3,16 +3,43 @@
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
-use git2::{ObjectType, Oid, Repository};
+use git2::{Commit, ObjectType, Oid, Repository};
+use serde::Serialize;
 
+use crate::output::{self, Format, KeyInfo, ObjectInfo};
 use crate::utils;
 
+/// Signature created by the `raw sign` command, as described
+/// in JSON output.
+#[derive(Serialize)]
+struct RawSignature {
+    /// The key that made the signature.
+    key: KeyInfo,
+    /// The signed object.
+    object: ObjectInfo,
+    /// The id of the signature commit.
+    signature_oid: String,
+}
+
 /// Execute the `raw sign` command.
-pub fn command(key_path: PathBuf, rev: String) -> Result<()> {
+pub fn command(key_path: PathBuf, rev: String, format: Format) -> Result<()> {
     let repo = utils::open_repository()?;
-    for secret_key in utils::get_secret_keys(key_path)?.into_values() {
+    let mut signatures = Vec::new();
+    for (path, secret_key) in utils::get_secret_keys(key_path)? {
         let tree_oid = sign(&repo, &secret_key, &rev)?;
-        println!("{tree_oid}");
+        if !format.is_json() {
+            println!("{tree_oid}");
+            continue;
+        }
+        let signed_object = utils::TreeSignature::load_oid(&repo, tree_oid)?.dereference()?;
+        signatures.push(RawSignature {
+            key: KeyInfo::new(path, &secret_key.public_key()?)?,
+            object: ObjectInfo::new(&repo, signed_object),
+            signature_oid: tree_oid.to_string(),
+        });
+    }
+    if format.is_json() {
+        output::print_json("raw sign", &signatures)?;
     }
     Ok(())
 }
@@ -20,6 +47,32 @@ pub fn command(key_path: PathBuf, rev: String) -> Result<()> {
 /// Sign the revision `rev` with the given secret key, write the results
 /// to `repo` and return the object id of the resulting signature tree.
 pub fn sign(repo: &Repository, secret_key: &utils::PrivateKey, rev: &str) -> Result<Oid> {
+    sign_with_parents(repo, secret_key, rev, &[])
+}
+
+/// Like [`sign`], but the new signature commit descends from the
+/// `previous` signature it replaces, keeping it reachable for audit.
+/// Signatures in the v0 format are trees, which cannot be parents.
+pub fn sign_replacing(
+    repo: &Repository,
+    secret_key: &utils::PrivateKey,
+    rev: &str,
+    previous: Oid,
+) -> Result<Oid> {
+    match repo.find_commit(previous) {
+        Ok(previous) => sign_with_parents(repo, secret_key, rev, &[&previous]),
+        Err(_) => sign(repo, secret_key, rev),
+    }
+}
+
+/// Implementation of [`sign`], with the given `parents` of the
+/// signature commit.
+fn sign_with_parents(
+    repo: &Repository,
+    secret_key: &utils::PrivateKey,
+    rev: &str,
+    parents: &[&Commit<'_>],
+) -> Result<Oid> {
     let object = repo
         .revparse_single(rev)
         .context("Failed to look-up git object id")?;
@@ -85,7 +138,7 @@ pub fn sign(repo: &Repository, secret_key: &utils::PrivateKey, rev: &str) -> Res
             &commit_author,
             &format!("git-signify signature over {rev}"),
             &tree,
-            &[],
+            parents,
         )
         .context("Failed to create git signature commit")?;
 
@@ -5,26 +5,82 @@ use std::path::PathBuf;
 use anyhow::Result;
 use either::*;
 use git2::{Oid, Repository};
+use serde::Serialize;
 
+use crate::output::{self, Format, KeyInfo, ObjectInfo, VerificationStatus};
 use crate::utils;
 
+/// Signature verified by the `raw verify` command, as described
+/// in JSON output.
+#[derive(Serialize)]
+struct RawVerification {
+    /// The key the signature was verified with.
+    key: KeyInfo,
+    /// The outcome of the verification.
+    status: VerificationStatus,
+    /// The signed object.
+    object: Option<ObjectInfo>,
+    /// Why the verification failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
 /// Execute the `raw verify` command.
-pub fn command(key_path: PathBuf, recover: bool, tree_rev: String) -> Result<()> {
+pub fn command(key_path: PathBuf, recover: bool, tree_rev: String, format: Format) -> Result<()> {
     let repo = utils::open_repository()?;
+
+    if format.is_json() {
+        return print_json(&repo, key_path, &tree_rev);
+    }
+
     for public_key in utils::get_public_keys(key_path)?.into_values() {
-        verify(&repo, &public_key, &tree_rev, recover)?.either(
+        let recovered_oid = verify(&repo, &public_key, &tree_rev, recover)?.either(
             |_| anyhow::bail!("No signature found for tree {tree_rev}"),
-            |recovered_oid| {
-                if let Some(recovered_oid) = recovered_oid {
-                    println!("{recovered_oid}");
-                }
-                Ok(())
-            },
+            Ok,
         )?;
+        if let Some(recovered_oid) = recovered_oid {
+            println!("{recovered_oid}");
+        }
     }
     Ok(())
 }
 
+/// Describe the verification of `tree_rev` with each key at `key_path`
+/// as JSON, failing after it is printed if some signature is invalid.
+fn print_json(repo: &Repository, key_path: PathBuf, tree_rev: &str) -> Result<()> {
+    let mut verifications = Vec::new();
+    for (path, public_key) in utils::get_public_keys(key_path)? {
+        let (status, object, error) = match verify(repo, &public_key, tree_rev, true) {
+            Ok(Right(recovered_oid)) => (
+                VerificationStatus::Valid,
+                recovered_oid.map(|oid| ObjectInfo::new(repo, oid)),
+                None,
+            ),
+            Ok(Left(())) => (
+                VerificationStatus::Missing,
+                None,
+                Some(format!("No signature found for tree {tree_rev}")),
+            ),
+            Err(e) => (VerificationStatus::Invalid, None, Some(format!("{e:#}"))),
+        };
+        verifications.push(RawVerification {
+            key: KeyInfo::new(path, &public_key)?,
+            status,
+            object,
+            error,
+        });
+    }
+
+    output::print_json("raw verify", &verifications)?;
+
+    let failed = verifications
+        .iter()
+        .filter(|verification| verification.error.is_some())
+        .count();
+    anyhow::ensure!(failed == 0, "Failed to verify {failed} signatures");
+    Ok(())
+}
+
 /// Verify the signature under `tree_rev` with the given public key.
 pub fn verify(
     repo: &Repository,
@@ -0,0 +1,380 @@
+//! Exchange data with remote repositories through [`git2`].
+
+use std::env;
+use std::error;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use git2::{
+    Cred, CredentialType, Direction, FetchOptions, FetchPrune, Oid, ProxyOptions, PushOptions,
+    Remote, RemoteCallbacks, Repository,
+};
+
+/// Reference advertised by a remote repository.
+pub struct RemoteRef {
+    /// The name of the reference.
+    pub name: String,
+    /// The object the reference points at.
+    pub oid: Oid,
+}
+
+/// Reference update rejected by a remote repository.
+#[derive(Debug)]
+pub struct RejectedRef {
+    /// The name of the rejected reference.
+    pub refname: String,
+    /// The reason reported by the remote.
+    pub reason: String,
+}
+
+/// Error returned when a remote repository rejects some of
+/// the pushed reference updates.
+#[derive(Debug)]
+pub struct PushRejected {
+    /// The name of the remote repository.
+    pub remote: String,
+    /// The rejected reference updates.
+    pub rejected: Vec<RejectedRef>,
+}
+
+impl fmt::Display for PushRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Remote {} rejected the update of:", self.remote)?;
+        for RejectedRef { refname, reason } in &self.rejected {
+            write!(f, "\n  - {refname} ({reason})")?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for PushRejected {}
+
+/// Fetch the given refspecs from `remote_name`. If `prune` is set, local
+/// references matching the refspecs which no longer exist on the remote
+/// are deleted.
+pub fn fetch<S: AsRef<str>>(
+    repo: &Repository,
+    remote_name: &str,
+    refspecs: &[S],
+    prune: bool,
+) -> Result<()> {
+    let mut remote = find_remote(repo, remote_name)?;
+    let config = repo.config().context("Failed to open git config")?;
+
+    let refspecs: Vec<&str> = refspecs.iter().map(AsRef::as_ref).collect();
+
+    let mut options = FetchOptions::new();
+    options
+        .remote_callbacks(remote_callbacks(&config))
+        .proxy_options(proxy_options())
+        .prune(if prune {
+            FetchPrune::On
+        } else {
+            FetchPrune::Unspecified
+        });
+
+    remote
+        .fetch(&refspecs, Some(&mut options), None)
+        .with_context(|| format!("Failed to fetch from remote {remote_name}"))
+}
+
+/// Push the given refspecs to `remote_name`. Reference updates rejected
+/// by the remote are reported with a [`PushRejected`] error.
+pub fn push<S: AsRef<str>>(repo: &Repository, remote_name: &str, refspecs: &[S]) -> Result<()> {
+    if refspecs.is_empty() {
+        return Ok(());
+    }
+
+    let mut remote = find_remote(repo, remote_name)?;
+    let config = repo.config().context("Failed to open git config")?;
+
+    let refspecs: Vec<&str> = refspecs.iter().map(AsRef::as_ref).collect();
+    let mut rejected = Vec::new();
+
+    {
+        let mut callbacks = remote_callbacks(&config);
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(reason) = status {
+                rejected.push(RejectedRef {
+                    refname: refname.to_owned(),
+                    reason: reason.to_owned(),
+                });
+            }
+            Ok(())
+        });
+
+        let mut options = PushOptions::new();
+        options
+            .remote_callbacks(callbacks)
+            .proxy_options(proxy_options());
+
+        remote
+            .push(&refspecs, Some(&mut options))
+            .with_context(|| format!("Failed to push to remote {remote_name}"))?;
+    }
+
+    if rejected.is_empty() {
+        Ok(())
+    } else {
+        Err(PushRejected {
+            remote: remote_name.to_owned(),
+            rejected,
+        }
+        .into())
+    }
+}
+
+/// Push the given refspecs to `remote_name` atomically: either every
+/// reference is updated, or none is. libgit2 does not support atomic
+/// pushes, so this runs `git push --atomic`, which fails if the remote
+/// does not advertise the capability. Unlike [`push`], credentials come
+/// from git's own credential helpers. The `pre-push` hook is skipped,
+/// as the one installed by `git signify install-hooks` would push the
+/// signatures on its own, outside of the atomic transaction.
+pub fn push_atomic<S: AsRef<str>>(
+    repo: &Repository,
+    remote_name: &str,
+    refspecs: &[S],
+) -> Result<()> {
+    if refspecs.is_empty() {
+        return Ok(());
+    }
+
+    let status = Command::new("git")
+        .arg("--git-dir")
+        .arg(repo.path())
+        .args(["push", "--atomic", "--no-verify", "--quiet", remote_name])
+        .args(refspecs.iter().map(AsRef::as_ref))
+        .status()
+        .context("Failed to run git push")?;
+    anyhow::ensure!(
+        status.success(),
+        "Failed to push atomically to remote {remote_name}"
+    );
+
+    Ok(())
+}
+
+/// Delete the given references from `remote_name`.
+pub fn delete<S: AsRef<str>>(repo: &Repository, remote_name: &str, refnames: &[S]) -> Result<()> {
+    let refspecs: Vec<String> = refnames
+        .iter()
+        .map(|refname| format!(":{}", refname.as_ref()))
+        .collect();
+    push(repo, remote_name, &refspecs)
+}
+
+/// List the references advertised by `remote_name`.
+pub fn list(repo: &Repository, remote_name: &str) -> Result<Vec<RemoteRef>> {
+    let mut remote = find_remote(repo, remote_name)?;
+    let config = repo.config().context("Failed to open git config")?;
+
+    let connection = remote
+        .connect_auth(
+            Direction::Fetch,
+            Some(remote_callbacks(&config)),
+            Some(proxy_options()),
+        )
+        .with_context(|| format!("Failed to connect to remote {remote_name}"))?;
+
+    let refs = connection
+        .list()
+        .context("Failed to look-up remote refs")?
+        .iter()
+        .map(|head| RemoteRef {
+            name: head.name().to_owned(),
+            oid: head.oid(),
+        })
+        .collect();
+
+    Ok(refs)
+}
+
+/// Build refspecs pushing each local reference matching `glob` to
+/// the reference with the same name on the remote.
+pub fn local_refspecs(repo: &Repository, glob: &str) -> Result<Vec<String>> {
+    let mut refspecs = Vec::new();
+
+    for maybe_ref in repo
+        .references_glob(glob)
+        .with_context(|| format!("Failed to look-up refs matching {glob}"))?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        let refname = reference.name().context("Invalid reference name")?;
+        refspecs.push(format!("{refname}:{refname}"));
+    }
+
+    Ok(refspecs)
+}
+
+/// Print the given refspecs, e.g. for dry runs.
+pub fn print_refspecs<S: AsRef<str>>(refspecs: &[S]) {
+    if refspecs.is_empty() {
+        println!("No refspecs selected");
+        return;
+    }
+    println!("Refspecs:");
+    for refspec in refspecs {
+        println!("  - {}", refspec.as_ref());
+    }
+}
+
+/// Look-up a remote by name, or create an anonymous remote, if
+/// `remote_name` is a url.
+fn find_remote<'repo>(repo: &'repo Repository, remote_name: &str) -> Result<Remote<'repo>> {
+    match repo.find_remote(remote_name) {
+        Ok(remote) => Ok(remote),
+        Err(_) if remote_name.contains(':') || remote_name.contains('/') => repo
+            .remote_anonymous(remote_name)
+            .with_context(|| format!("Invalid remote url {remote_name}")),
+        Err(e) => Err(e).with_context(|| format!("Unable to find remote {remote_name}")),
+    }
+}
+
+fn proxy_options() -> ProxyOptions<'static> {
+    let mut opts = ProxyOptions::new();
+    opts.auto();
+    opts
+}
+
+/// Build the callbacks used to authenticate with remotes, and to
+/// report the progress of transfers on an interactive terminal.
+fn remote_callbacks(config: &git2::Config) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut credentials = CredentialProvider::new(config);
+
+    callbacks.credentials(move |url, username_from_url, allowed_types| {
+        credentials.next(url, username_from_url, allowed_types)
+    });
+
+    if io::stderr().is_terminal() {
+        let mut received_all = false;
+        let mut written_all = false;
+
+        callbacks
+            .sideband_progress(|data| {
+                eprint!("remote: {}", String::from_utf8_lossy(data));
+                true
+            })
+            .transfer_progress(move |progress| {
+                let received = progress.received_objects();
+                let total = progress.total_objects();
+                if !received_all {
+                    // the totals are unknown until the pack is received
+                    received_all = total > 0 && received == total;
+                    eprint!("\rReceiving objects: {received}/{total}");
+                    if received_all {
+                        eprintln!(", done.");
+                    }
+                    _ = io::stderr().flush();
+                }
+                true
+            })
+            .push_transfer_progress(move |current, total, _bytes| {
+                if !written_all {
+                    written_all = total > 0 && current == total;
+                    eprint!("\rWriting objects: {current}/{total}");
+                    if written_all {
+                        eprintln!(", done.");
+                    }
+                    _ = io::stderr().flush();
+                }
+            });
+    }
+
+    callbacks
+}
+
+/// Provider of credentials to authenticate with remotes. Each method
+/// is attempted at most once, since libgit2 keeps asking for credentials
+/// until authentication succeeds.
+struct CredentialProvider<'cfg> {
+    config: &'cfg git2::Config,
+    tried_username: bool,
+    tried_ssh_agent: bool,
+    tried_credential_helper: bool,
+    tried_askpass: bool,
+    tried_default: bool,
+}
+
+impl<'cfg> CredentialProvider<'cfg> {
+    fn new(config: &'cfg git2::Config) -> Self {
+        Self {
+            config,
+            tried_username: false,
+            tried_ssh_agent: false,
+            tried_credential_helper: false,
+            tried_askpass: false,
+            tried_default: false,
+        }
+    }
+
+    /// Return the next credentials to attempt authenticating with:
+    /// the ssh-agent, git credential helpers and `GIT_ASKPASS`.
+    fn next(
+        &mut self,
+        url: &str,
+        username_from_url: Option<&str>,
+        allowed_types: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        let username = username_from_url.unwrap_or("git");
+
+        if allowed_types.contains(CredentialType::USERNAME) && !self.tried_username {
+            self.tried_username = true;
+            return Cred::username(username);
+        }
+        if allowed_types.contains(CredentialType::SSH_KEY) && !self.tried_ssh_agent {
+            self.tried_ssh_agent = true;
+            return Cred::ssh_key_from_agent(username);
+        }
+        if allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if !self.tried_credential_helper {
+                self.tried_credential_helper = true;
+                if let Ok(cred) = Cred::credential_helper(self.config, url, username_from_url) {
+                    return Ok(cred);
+                }
+            }
+            if !self.tried_askpass {
+                self.tried_askpass = true;
+                if let Some(cred) = self.askpass(url, username_from_url) {
+                    return Ok(cred);
+                }
+            }
+        }
+        if allowed_types.contains(CredentialType::DEFAULT) && !self.tried_default {
+            self.tried_default = true;
+            return Cred::default();
+        }
+
+        Err(git2::Error::from_str(&format!(
+            "Exhausted all credentials to authenticate with {url}"
+        )))
+    }
+
+    /// Prompt for a username and password with the program in
+    /// `GIT_ASKPASS`, `core.askPass` or `SSH_ASKPASS`, like git does.
+    fn askpass(&self, url: &str, username_from_url: Option<&str>) -> Option<Cred> {
+        let program = env::var_os("GIT_ASKPASS")
+            .or_else(|| self.config.get_string("core.askPass").ok().map(Into::into))
+            .or_else(|| env::var_os("SSH_ASKPASS"))?;
+
+        let prompt = |prompt: String| {
+            let output = Command::new(&program).arg(prompt).output().ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            let answer = String::from_utf8(output.stdout).ok()?;
+            Some(answer.trim_end_matches(['\r', '\n']).to_owned())
+        };
+
+        let username = match username_from_url {
+            Some(username) => username.to_owned(),
+            None => prompt(format!("Username for '{url}': "))?,
+        };
+        let password = prompt(format!("Password for '{url}': "))?;
+
+        Cred::userpass_plaintext(&username, &password).ok()
+    }
+}
@@ -4,24 +4,70 @@
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
+use serde::Serialize;
 
+use crate::output::{self, Format, KeyInfo, VerificationStatus};
 use crate::utils;
 
+/// Signature found by the `rev-lookup` command, as described
+/// in JSON output.
+#[derive(Serialize)]
+struct FoundSignature {
+    /// The key that made the signature.
+    key: KeyInfo,
+    /// The reference the signature is stored under.
+    signature_ref: String,
+    /// Whether the signature verified.
+    status: VerificationStatus,
+    /// The reason why verification failed, if it did.
+    error: Option<String>,
+}
+
 /// Execute the `rev-lookup` command.
-pub fn command(key_path: PathBuf, rev: String) -> Result<()> {
+pub fn command(key_path: PathBuf, rev: String, format: Format) -> Result<()> {
     let repo = utils::open_repository()?;
     let object_oid = repo
         .revparse_single(&rev)
         .context("Failed to look-up git object")?
         .id();
-    for public_key in utils::get_public_keys(key_path)?.into_values() {
-        let tree_rev = {
-            let key_fingerprint = public_key.fingerprint()?;
-            utils::craft_signature_reference(key_fingerprint, object_oid)
-        };
-        if utils::revparse_single_ok_or_else(&repo, &tree_rev, |_| Ok(true), || Ok(false))? {
-            println!("{tree_rev}");
+    let mut signatures = Vec::new();
+    for (path, public_key) in utils::get_public_keys(key_path)? {
+        for key_fingerprint in public_key.equivalent_fingerprints()? {
+            let tree_rev = utils::craft_signature_reference(key_fingerprint, object_oid);
+            let Some(signature_oid) = utils::revparse_single_ok_or_else(
+                &repo,
+                &tree_rev,
+                |obj| Ok(Some(obj.id())),
+                || Ok(None),
+            )?
+            else {
+                continue;
+            };
+            let verification =
+                utils::verify_signature_over(&repo, &public_key, signature_oid, object_oid);
+            if !format.is_json() {
+                match verification {
+                    Ok(()) => println!("{tree_rev}"),
+                    Err(e) => {
+                        eprintln!("Ignoring invalid signature stored under {tree_rev}: {e:#}")
+                    }
+                }
+                continue;
+            }
+            let (status, error) = match verification {
+                Ok(()) => (VerificationStatus::Valid, None),
+                Err(e) => (VerificationStatus::Invalid, Some(format!("{e:#}"))),
+            };
+            signatures.push(FoundSignature {
+                key: KeyInfo::new(path.clone(), &public_key)?,
+                signature_ref: tree_rev,
+                status,
+                error,
+            });
         }
     }
+    if format.is_json() {
+        output::print_json("rev-lookup", &signatures)?;
+    }
     Ok(())
 }
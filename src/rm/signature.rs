@@ -1,58 +1,179 @@
 //! Remove git-signify signatures.
 
-use std::fs;
-use std::io;
+use std::collections::BTreeSet;
 use std::path::PathBuf;
-use std::process::Command;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
 
+use crate::history::{self, Event};
+use crate::log;
+use crate::remote;
 use crate::utils;
 
+/// Signatures selected for removal.
+pub enum Target {
+    /// The signatures made by the given keys over a revision.
+    Signature {
+        /// Path to the public keys of the signers.
+        key_path: PathBuf,
+        /// The signed revision.
+        rev: String,
+    },
+    /// All signatures over a revision.
+    Revision(String),
+    /// All signatures made by the given keys.
+    Key(PathBuf),
+    /// All signify references matching a glob.
+    Glob(String),
+}
+
+/// Resolved form of a [`Target`], matched against reference names.
+enum Selection {
+    Signers {
+        fingerprints: BTreeSet<Oid>,
+        oid: Option<Oid>,
+    },
+    Revision(Oid),
+    Glob(String),
+}
+
 /// Execute the `rm signature` command.
-pub fn command(public_key: PathBuf, rev: String, remote: Option<String>) -> Result<()> {
+pub fn command(target: Target, remote: Option<String>) -> Result<()> {
     let repo = utils::open_repository()?;
+    let selection = Selection::resolve(&repo, target)?;
 
-    for public_key in utils::get_public_keys(public_key)?.into_values() {
-        let tree_rev = {
-            let object_oid = repo
-                .revparse_single(&rev)
-                .context("Failed to look-up git object")?
-                .id();
-            let key_fingerprint = public_key.fingerprint()?;
-            utils::craft_signature_reference(key_fingerprint, object_oid)
-        };
+    let refnames: Vec<String> = if let Some(remote) = remote.as_ref() {
+        remote::list(&repo, remote)?
+            .into_iter()
+            .map(|remote_ref| remote_ref.name)
+            .filter(|refname| selection.matches(refname))
+            .collect()
+    } else {
+        let mut refnames = Vec::new();
+        for maybe_ref in repo
+            .references_glob(utils::ALL_SIGNIFY_REFS)
+            .context("Failed to look-up git-signify refs")?
+        {
+            let reference = maybe_ref.context("Failed to parse git reference")?;
+            let refname = reference.name().context("Invalid reference name")?;
+            if selection.matches(refname) {
+                refnames.push(refname.to_owned());
+            }
+        }
+        refnames
+    };
 
-        if let Some(remote) = remote.as_ref() {
-            let exit_code = Command::new("git")
-                .arg("push")
-                .arg("-d")
-                .arg(remote)
-                .arg(tree_rev)
-                .spawn()
-                .context("Failed to spawn git command to remove remote signature")?
-                .wait()
-                .context("Failed to wait for git command to remove remote signature")?;
-            if !exit_code.success() {
-                return Err(anyhow!("Exit code of git: {exit_code}"));
+    if refnames.is_empty() {
+        println!("No signatures found to remove");
+        return Ok(());
+    }
+
+    if let Some(remote) = remote.as_ref() {
+        remote::delete(&repo, remote, &refnames).context("Failed to remove remote signature")?;
+    } else {
+        for refname in &refnames {
+            let mut reference = repo
+                .find_reference(refname)
+                .context("Failed to look-up local git reference")?;
+            if let Some(signature_oid) = reference.target() {
+                history::record(&repo, "rm", Event::Removed, refname, signature_oid)?;
             }
-        } else {
-            let mut path = PathBuf::new();
-
-            path.push(".git");
-            path.push(tree_rev);
-
-            fs::remove_file(path)
-                .or_else(|e| {
-                    if e.kind() == io::ErrorKind::NotFound {
-                        Ok(())
-                    } else {
-                        Err(e)
-                    }
-                })
+            reference
+                .delete()
                 .context("Failed to remove local git reference")?;
         }
     }
 
+    println!("Removed signatures:");
+    for refname in &refnames {
+        println!("  - {refname}");
+    }
+
     Ok(())
 }
+
+impl Selection {
+    /// Resolve the keys and revisions of a [`Target`].
+    fn resolve(repo: &Repository, target: Target) -> Result<Self> {
+        let fingerprints = |key_path| -> Result<BTreeSet<Oid>> {
+            let mut fingerprints = BTreeSet::new();
+            for public_key in utils::get_public_keys(key_path)?.into_values() {
+                fingerprints.extend(public_key.equivalent_fingerprints()?);
+            }
+            Ok(fingerprints)
+        };
+        let object_oid = |rev: &str| -> Result<Oid> {
+            Ok(repo
+                .revparse_single(rev)
+                .context("Failed to look-up git object")?
+                .id())
+        };
+
+        Ok(match target {
+            Target::Signature { key_path, rev } => Self::Signers {
+                fingerprints: fingerprints(key_path)?,
+                oid: Some(object_oid(&rev)?),
+            },
+            Target::Revision(rev) => Self::Revision(object_oid(&rev)?),
+            Target::Key(key_path) => Self::Signers {
+                fingerprints: fingerprints(key_path)?,
+                oid: None,
+            },
+            Target::Glob(glob) => {
+                anyhow::ensure!(
+                    glob.starts_with("refs/signify/"),
+                    "Only references under refs/signify/ may be removed, got {glob}"
+                );
+                Self::Glob(glob)
+            }
+        })
+    }
+
+    /// Check if the reference `refname` is selected for removal.
+    fn matches(&self, refname: &str) -> bool {
+        if let Self::Glob(glob) = self {
+            // the history and the append-only log are kept for audit,
+            // even when removing by glob
+            return refname != history::HISTORY_REF
+                && refname != log::entry::LOG_REF
+                && utils::glob_matches(glob, refname);
+        }
+
+        let Some((signer, signed_oid)) = refname
+            .strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+            .and_then(utils::parse_signer_and_oid)
+        else {
+            return false;
+        };
+
+        match self {
+            Self::Signers { fingerprints, oid } => {
+                fingerprints.contains(&signer) && oid.is_none_or(|oid| oid == signed_oid)
+            }
+            Self::Revision(oid) => *oid == signed_oid,
+            Self::Glob(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_removal_keeps_log_and_history() {
+        let selection = Selection::Glob("refs/signify/*".to_owned());
+        let signature = format!(
+            "{}{}/{}",
+            utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX,
+            "a".repeat(40),
+            "b".repeat(40)
+        );
+
+        assert!(selection.matches(&signature));
+        assert!(selection.matches("refs/signify/keys/release"));
+        assert!(!selection.matches(log::entry::LOG_REF));
+        assert!(!selection.matches(history::HISTORY_REF));
+    }
+}
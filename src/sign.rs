@@ -1,43 +1,199 @@
 //! Create signatures with [`libsignify`] and store references
 //! to them in git.
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
+use git2::Repository;
+use serde::Serialize;
 
-use crate::raw::sign::sign;
-use crate::utils;
+use crate::history::{self, Event};
+use crate::output::{self, Format, KeyInfo, ObjectInfo};
+use crate::raw::sign::{sign, sign_replacing};
+use crate::utils::{self, PrivateKey};
 
-/// Execute the `sign` command.
-pub fn command(key_path: PathBuf, rev: String) -> Result<()> {
+/// Signature made by the `sign` command, as described in JSON output.
+#[derive(Serialize)]
+struct Signature {
+    /// The key that made the signature.
+    key: KeyInfo,
+    /// The signed object.
+    object: ObjectInfo,
+    /// The reference naming the signed object, when signing refs
+    /// matching a glob.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refname: Option<String>,
+    /// The reference the signature is stored under.
+    signature_ref: String,
+    /// The id of the signature commit.
+    signature_oid: String,
+    /// Whether the signature was created, or already existed.
+    status: SignStatus,
+}
+
+/// Outcome of signing with a key.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SignStatus {
+    /// A new signature was created.
+    Created,
+    /// A signature by the key already existed.
+    Exists,
+    /// A signature by the key already existed, and was replaced.
+    Replaced,
+}
+
+/// Execute the `sign` command, over `rev`, or over the target of every
+/// reference matching `refs_glob`. Objects already signed by a key are
+/// skipped, unless `force` is set, in which case their signature is
+/// replaced.
+pub fn command(
+    key_path: PathBuf,
+    rev: Option<String>,
+    refs_glob: Option<String>,
+    force: bool,
+    format: Format,
+) -> Result<()> {
     let repo = utils::open_repository()?;
-    for (path, secret_key) in utils::get_secret_keys(key_path)? {
+    let secret_keys = utils::get_secret_keys(key_path)?;
+
+    let Some(refs_glob) = refs_glob else {
+        let rev = rev.context("No revision to sign")?;
+        let signatures = sign_revision(&repo, &secret_keys, &rev, None, force, !format.is_json())?;
+        if format.is_json() {
+            output::print_json("sign", &signatures)?;
+        }
+        return Ok(());
+    };
+
+    let mut signatures = Vec::new();
+    for refname in utils::matching_refs(&repo, &refs_glob)? {
+        signatures.extend(sign_revision(
+            &repo,
+            &secret_keys,
+            &refname,
+            Some(&refname),
+            force,
+            false,
+        )?);
+    }
+
+    if format.is_json() {
+        return output::print_json("sign", &signatures);
+    }
+    if signatures.is_empty() {
+        println!("No refs match {refs_glob}");
+        return Ok(());
+    }
+
+    let rows: Vec<_> = signatures
+        .into_iter()
+        .map(|signature| {
+            [
+                signature.refname.unwrap_or_default(),
+                signature.object.oid[..7].to_owned(),
+                signature.key.key_path.display().to_string(),
+                match signature.status {
+                    SignStatus::Created => "signed",
+                    SignStatus::Exists => "already signed",
+                    SignStatus::Replaced => "replaced",
+                }
+                .to_owned(),
+            ]
+        })
+        .collect();
+    output::print_table(["REF", "OBJECT", "KEY", "STATUS"], &rows);
+
+    Ok(())
+}
+
+/// Sign `rev` with each of `secret_keys`, unless they already signed
+/// it and `force` is not set. Progress is printed if `verbose` is set.
+fn sign_revision(
+    repo: &Repository,
+    secret_keys: &BTreeMap<PathBuf, PrivateKey>,
+    rev: &str,
+    refname: Option<&str>,
+    force: bool,
+    verbose: bool,
+) -> Result<Vec<Signature>> {
+    let mut signatures = Vec::new();
+    for (path, secret_key) in secret_keys {
         let signed_object = repo
-            .revparse_single(&rev)
+            .revparse_single(rev)
             .context("Failed to look-up object to sign")?
             .id();
-        let key_fingerprint = secret_key.public_key()?.fingerprint()?;
+        let public_key = secret_key.public_key()?;
+        let key_fingerprint = public_key.fingerprint()?;
         let reference = utils::craft_signature_reference(key_fingerprint, signed_object);
-        if utils::revparse_single_ok_or_else(&repo, &reference, |_| Ok(true), || Ok(false))? {
-            println!("Signature already exists with key:");
-            println!("  - {}", path.display());
-            println!("Signature stored under:");
-            println!("  - {reference}");
-            continue;
-        }
-        let tree_oid = sign(&repo, &secret_key, &rev)?;
-        repo.reference(
-            &reference, tree_oid,
-            // references to signatures are non-deterministic,
-            // so we should fail if we attempt to overwrite a
-            // signature in our local git repository
-            false, "",
-        )
-        .context("Failed to store reference to signature")?;
-        println!("Signed with key:");
-        println!("  - {}", path.display());
-        println!("Signature stored under:");
-        println!("  - {reference}");
+        let existing = utils::revparse_single_ok_or_else(
+            repo,
+            &reference,
+            |obj| Ok(Some(obj.id())),
+            || Ok(None),
+        )?;
+        let (tree_oid, status) = if let Some(previous) = existing.filter(|_| force) {
+            let tree_oid = sign_replacing(repo, secret_key, rev, previous)?;
+            // signature refs are not logged by default, but the history
+            // of replaced signatures must be kept for audit
+            repo.reference_ensure_log(&reference)
+                .context("Failed to create reflog of signature reference")?;
+            repo.reference_matching(
+                &reference,
+                tree_oid,
+                true,
+                previous,
+                &format!("git-signify sign: replace signature {previous}"),
+            )
+            .context("Failed to replace reference to signature")?;
+            history::record(repo, "sign", Event::Replaced, &reference, tree_oid)?;
+            if verbose {
+                println!("Replaced signature with key:");
+                println!("  - {}", path.display());
+                println!("Signature stored under:");
+                println!("  - {reference}");
+                println!("Previous signature kept as parent:");
+                println!("  - {previous}");
+            }
+            (tree_oid, SignStatus::Replaced)
+        } else if let Some(tree_oid) = existing {
+            if verbose {
+                println!("Signature already exists with key:");
+                println!("  - {}", path.display());
+                println!("Signature stored under:");
+                println!("  - {reference}");
+            }
+            (tree_oid, SignStatus::Exists)
+        } else {
+            let tree_oid = sign(repo, secret_key, rev)?;
+            repo.reference(
+                &reference,
+                tree_oid,
+                // references to signatures are non-deterministic,
+                // so we should fail if we attempt to overwrite a
+                // signature in our local git repository
+                false,
+                "git-signify sign: add signature",
+            )
+            .context("Failed to store reference to signature")?;
+            history::record(repo, "sign", Event::Added, &reference, tree_oid)?;
+            if verbose {
+                println!("Signed with key:");
+                println!("  - {}", path.display());
+                println!("Signature stored under:");
+                println!("  - {reference}");
+            }
+            (tree_oid, SignStatus::Created)
+        };
+        signatures.push(Signature {
+            key: KeyInfo::new(path.clone(), &public_key)?,
+            object: ObjectInfo::new(repo, signed_object),
+            refname: refname.map(str::to_owned),
+            signature_ref: reference,
+            signature_oid: tree_oid.to_string(),
+            status,
+        });
     }
-    Ok(())
+    Ok(signatures)
 }
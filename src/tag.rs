@@ -0,0 +1,218 @@
+//! Create annotated tags and sign them in one step.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use git2::{Oid, Reference};
+use serde::Serialize;
+
+use crate::history::{self, Event};
+use crate::output::{self, Format, KeyInfo, ObjectInfo};
+use crate::raw::sign::sign;
+use crate::remote;
+use crate::utils;
+
+/// Tag created by the `tag` command, as described in JSON output.
+#[derive(Serialize)]
+struct Tag {
+    /// The reference of the tag.
+    tag_ref: String,
+    /// The annotated tag object.
+    object: ObjectInfo,
+    /// Signatures made over the tag.
+    signatures: Vec<TagSignature>,
+    /// Signatures over the tag replaced with `--force`, which were removed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    removed_signatures: Vec<String>,
+    /// The remote the tag and its signatures were pushed to, if any.
+    remote: Option<String>,
+}
+
+/// Signature made over a tag, as described in JSON output.
+#[derive(Serialize)]
+struct TagSignature {
+    /// The key that made the signature.
+    key: KeyInfo,
+    /// The reference the signature is stored under.
+    signature_ref: String,
+}
+
+/// Execute the `tag` command. Creates the annotated tag `name` over
+/// `rev`, and signs it with every key at `key_path`. If a `remote` is
+/// given, the tag and its signatures are pushed to it atomically. A tag
+/// replaced with `force` loses its signatures, locally and on the remote.
+pub fn command(
+    key_path: PathBuf,
+    name: String,
+    rev: String,
+    message: String,
+    force: bool,
+    remote: Option<String>,
+    format: Format,
+) -> Result<()> {
+    let repo = utils::open_repository()?;
+    let secret_keys = utils::get_secret_keys(key_path)?;
+    anyhow::ensure!(!secret_keys.is_empty(), "No secret keys to sign with");
+
+    let target = repo
+        .revparse_single(&rev)
+        .with_context(|| format!("Failed to look-up revision {rev}"))?;
+    let tagger = repo
+        .signature()
+        .context("Failed to determine the tagger identity")?;
+    let message = if message.ends_with('\n') {
+        message
+    } else {
+        format!("{message}\n")
+    };
+    let tag_ref = format!("refs/tags/{name}");
+    anyhow::ensure!(
+        Reference::is_valid_name(&tag_ref),
+        "Invalid tag name {name:?}"
+    );
+    let replaced = repo.refname_to_id(&tag_ref).ok();
+    anyhow::ensure!(
+        force || replaced.is_none(),
+        "Tag {name} already exists, pass --force to replace it"
+    );
+
+    // the tag object is signed before the tag ref is created, such that
+    // failing signers do not leave unsigned tags behind
+    let tag_oid = repo
+        .tag_annotation_create(&name, &target, &tagger, &message)
+        .with_context(|| format!("Failed to create tag {name}"))?;
+    let mut signed = Vec::new();
+    for (path, secret_key) in secret_keys {
+        let public_key = secret_key.public_key()?;
+        let reference = utils::craft_signature_reference(public_key.fingerprint()?, tag_oid);
+        let tree_oid = sign(&repo, &secret_key, &tag_oid.to_string())?;
+        signed.push((path, public_key, reference, tree_oid));
+    }
+
+    repo.reference(
+        &tag_ref,
+        tag_oid,
+        force,
+        &format!("git-signify tag: create tag {name}"),
+    )
+    .with_context(|| format!("Failed to create tag {name}"))?;
+    if !format.is_json() {
+        println!("Created tag:");
+        println!("  - {tag_ref}");
+    }
+
+    // signatures over the replaced tag object no longer sign any tag
+    let mut removed_signatures = Vec::new();
+    if let Some(replaced) = replaced.filter(|&replaced| replaced != tag_oid) {
+        let refnames = repo
+            .references_glob(utils::ALL_SIGNIFY_SIGNATURE_REFS)
+            .context("Failed to look-up signature refs")?
+            .names()
+            .map(|refname| refname.map(str::to_owned))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to parse git reference")?;
+        for refname in signatures_over(refnames, replaced) {
+            let mut reference = repo
+                .find_reference(&refname)
+                .context("Failed to look-up local git reference")?;
+            if let Some(signature_oid) = reference.target() {
+                history::record(&repo, "tag", Event::Removed, &refname, signature_oid)?;
+            }
+            reference
+                .delete()
+                .context("Failed to remove signature of the replaced tag")?;
+            removed_signatures.push(refname);
+        }
+        if !format.is_json() && !removed_signatures.is_empty() {
+            println!("Removed signatures of the replaced tag:");
+            for refname in &removed_signatures {
+                println!("  - {refname}");
+            }
+        }
+    }
+
+    let mut signatures = Vec::new();
+    for (path, public_key, reference, tree_oid) in signed {
+        repo.reference(
+            &reference,
+            tree_oid,
+            false,
+            "git-signify tag: add signature",
+        )
+        .context("Failed to store reference to signature")?;
+        history::record(&repo, "tag", Event::Added, &reference, tree_oid)?;
+        if !format.is_json() {
+            println!("Signed with key:");
+            println!("  - {}", path.display());
+            println!("Signature stored under:");
+            println!("  - {reference}");
+        }
+        signatures.push(TagSignature {
+            key: KeyInfo::new(path, &public_key)?,
+            signature_ref: reference,
+        });
+    }
+
+    if let Some(remote) = &remote {
+        // a single push, such that `pre-receive` hooks see the tag
+        // along with its signatures, and the remote never gets the tag
+        // without them
+        let mut refspecs: Vec<_> = [&tag_ref]
+            .into_iter()
+            .chain(signatures.iter().map(|signature| &signature.signature_ref))
+            .map(|refname| format!("{refname}:{refname}"))
+            .collect();
+        if force {
+            refspecs[0].insert(0, '+');
+
+            // the remote tag may differ from the local one it replaces
+            let remote_refs = remote::list(&repo, remote)?;
+            let remote_tag = remote_refs
+                .iter()
+                .find(|remote_ref| remote_ref.name == tag_ref)
+                .map(|remote_ref| remote_ref.oid)
+                .filter(|&remote_tag| remote_tag != tag_oid);
+            if let Some(remote_tag) = remote_tag {
+                let refnames = remote_refs.into_iter().map(|remote_ref| remote_ref.name);
+                refspecs.extend(
+                    signatures_over(refnames, remote_tag)
+                        .into_iter()
+                        .map(|refname| format!(":{refname}")),
+                );
+            }
+        }
+        remote::push_atomic(&repo, remote, &refspecs)
+            .with_context(|| format!("Failed to push tag {name} and its signatures"))?;
+        if !format.is_json() {
+            println!("Pushed to remote {remote}");
+        }
+    }
+
+    if format.is_json() {
+        output::print_json(
+            "tag",
+            &Tag {
+                tag_ref,
+                object: ObjectInfo::new(&repo, tag_oid),
+                signatures,
+                removed_signatures,
+                remote,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Select the signature references over `oid` among `refnames`.
+fn signatures_over(refnames: impl IntoIterator<Item = String>, oid: Oid) -> Vec<String> {
+    refnames
+        .into_iter()
+        .filter(|refname| {
+            refname
+                .strip_prefix(utils::ALL_SIGNIFY_SIGNATURE_REFS_PREFIX)
+                .and_then(utils::parse_signer_and_oid)
+                .is_some_and(|(_, signed_oid)| signed_oid == oid)
+        })
+        .collect()
+}
@@ -4,11 +4,12 @@ use std::collections::BTreeMap;
 use std::error;
 use std::fmt;
 use std::fs;
-use std::io::Cursor;
+use std::io::{Cursor, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
 use anyhow::{anyhow, Context, Result};
-use git2::{Blob, ErrorCode, Object, ObjectType, Oid, Repository, RepositoryOpenFlags};
+use git2::{Blob, ErrorCode, Object, ObjectType, Oid, Repository};
 use libsignify::Codeable;
 use ml_signify::codec::{codecs, Decode as _, Encode as _};
 use zeroize::Zeroizing;
@@ -21,6 +22,8 @@ pub enum PrivateKey {
     Minisign(minisign::SecretKey),
     /// Private key originating from [`ml_signify`].
     MlSignify(Box<ml_signify::SigningKey>),
+    /// Private key held by an external signing helper.
+    External(ExternalSigner),
 }
 
 impl PrivateKey {
@@ -35,6 +38,7 @@ impl PrivateKey {
             Self::MlSignify(private_key) => {
                 Ok(PublicKey::MlSignify(Box::new(private_key.verifying_key())))
             }
+            Self::External(signer) => get_public_key(&signer.public_key_path),
         }
     }
 
@@ -62,6 +66,64 @@ impl PrivateKey {
                     ))
                     .into_bytes())
             }
+            Self::External(signer) => signer.sign(msg.as_ref()),
+        }
+    }
+
+    /// Convert this key to the format of the given algorithm. Like
+    /// [`PublicKey::convert`], only conversions between signify and
+    /// minisign keys are supported. The converted key is not encrypted.
+    pub fn convert(&self, algorithm: TreeSignatureAlgo) -> Result<Self> {
+        match (self, algorithm) {
+            (Self::Signify(private_key), TreeSignatureAlgo::Minisign) => {
+                let signify_bytes = Zeroizing::new(private_key.as_bytes());
+                let keynum = &signify_bytes[SIGNIFY_SECRET_KEYNUM];
+                let secret_key = &signify_bytes[SIGNIFY_SECRET_KEY];
+
+                let mut minisign_bytes = Zeroizing::new(vec![0u8; MINISIGN_SECRET_KEY_LEN]);
+                minisign_bytes[..2].copy_from_slice(b"Ed");
+                minisign_bytes[4..6].copy_from_slice(b"B2");
+                minisign_bytes[MINISIGN_SECRET_KEYNUM].copy_from_slice(keynum);
+                minisign_bytes[MINISIGN_SECRET_KEY].copy_from_slice(secret_key);
+
+                let checksum = {
+                    use blake2::Digest as _;
+
+                    let mut hasher = blake2::Blake2b::<blake2::digest::consts::U32>::new();
+                    hasher.update(b"Ed");
+                    hasher.update(keynum);
+                    hasher.update(secret_key);
+                    hasher.finalize()
+                };
+                minisign_bytes[MINISIGN_SECRET_CHECKSUM].copy_from_slice(&checksum);
+
+                minisign::SecretKey::from_bytes(&minisign_bytes)
+                    .map(Self::Minisign)
+                    .context("Failed to convert signify secret key to minisign")
+            }
+            (Self::Minisign(private_key), TreeSignatureAlgo::Signify) => {
+                let minisign_bytes = Zeroizing::new(private_key.to_bytes());
+                let secret_key = &minisign_bytes[MINISIGN_SECRET_KEY];
+
+                let mut signify_bytes = Zeroizing::new(vec![0u8; SIGNIFY_SECRET_KEY_LEN]);
+                signify_bytes[..2].copy_from_slice(b"Ed");
+                signify_bytes[2..4].copy_from_slice(b"BK");
+                signify_bytes[SIGNIFY_SECRET_CHECKSUM]
+                    .copy_from_slice(&signify_checksum(secret_key));
+                signify_bytes[SIGNIFY_SECRET_KEYNUM]
+                    .copy_from_slice(&minisign_bytes[MINISIGN_SECRET_KEYNUM]);
+                signify_bytes[SIGNIFY_SECRET_KEY].copy_from_slice(secret_key);
+
+                libsignify::PrivateKey::from_bytes(&signify_bytes)
+                    .map(Self::Signify)
+                    .map_err(Error::new)
+                    .context("Failed to convert minisign secret key to signify")
+            }
+            (private_key, algorithm) => anyhow::bail!(
+                "Cannot convert {} secret key to {}",
+                private_key.algorithm().as_str(),
+                algorithm.as_str()
+            ),
         }
     }
 
@@ -71,7 +133,120 @@ impl PrivateKey {
             Self::Signify(_) => TreeSignatureAlgo::Signify,
             Self::Minisign(_) => TreeSignatureAlgo::Minisign,
             Self::MlSignify(_) => TreeSignatureAlgo::MlSignify,
+            Self::External(signer) => signer.algorithm,
+        }
+    }
+}
+
+/// Signing helper program holding a private key on behalf of `git-signify`.
+///
+/// Helpers are configured with a key file of the following form, where
+/// `public-key` is resolved relative to the directory of the key file:
+///
+/// ```text
+/// untrusted comment: external signer
+/// program: vault-sign --key release
+/// public-key: release.pub
+/// ```
+///
+/// The `program` is run with `sh -c`, receives the raw message to sign on
+/// its standard input, and must write the encoded signature to its standard
+/// output, in the same format produced by the respective signing tool. The
+/// algorithm of the signature is the one of the helper's public key, which
+/// is exported to the helper as `GIT_SIGNIFY_ALGORITHM`, along with
+/// `GIT_SIGNIFY_PUBLIC_KEY` and `GIT_SIGNIFY_FINGERPRINT`.
+pub struct ExternalSigner {
+    /// Shell command of the signing helper.
+    program: String,
+    /// Path to the public key of the signing helper.
+    public_key_path: PathBuf,
+    /// Algorithm of the public key of the signing helper.
+    algorithm: TreeSignatureAlgo,
+}
+
+impl ExternalSigner {
+    /// Parse the configuration of an [`ExternalSigner`] from the contents
+    /// of the key file at `path`.
+    fn from_key_data(path: &Path, key_data: &str) -> Result<Self> {
+        let mut program = None;
+        let mut public_key_path = None;
+
+        for line in key_data.lines().skip(1) {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (field, value) = line
+                .split_once(':')
+                .with_context(|| format!("Invalid external signer entry {line:?}"))?;
+
+            match field.trim() {
+                "program" => program = Some(value.trim().to_owned()),
+                "public-key" => {
+                    let key_dir = path.parent().unwrap_or_else(|| Path::new("."));
+                    public_key_path = Some(key_dir.join(value.trim()));
+                }
+                field => anyhow::bail!("Unknown external signer field {field:?}"),
+            }
         }
+
+        let program = program.context("No program configured for external signer")?;
+        let public_key_path =
+            public_key_path.context("No public key configured for external signer")?;
+        let algorithm = get_public_key(&public_key_path)
+            .context("Failed to read public key of external signer")?
+            .algorithm();
+
+        Ok(Self {
+            program,
+            public_key_path,
+            algorithm,
+        })
+    }
+
+    /// Have the signing helper sign `msg`, and check the returned
+    /// signature against its public key.
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let public_key = get_public_key(&self.public_key_path)
+            .context("Failed to read public key of external signer")?;
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.program)
+            .env("GIT_SIGNIFY_ALGORITHM", self.algorithm.as_str())
+            .env("GIT_SIGNIFY_PUBLIC_KEY", &self.public_key_path)
+            .env(
+                "GIT_SIGNIFY_FINGERPRINT",
+                public_key.fingerprint()?.to_string(),
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to spawn external signer")?;
+
+        child
+            .stdin
+            .take()
+            .context("Failed to open standard input of external signer")?
+            .write_all(msg)
+            .context("Failed to write message to external signer")?;
+
+        let output = child
+            .wait_with_output()
+            .context("Failed to wait for external signer")?;
+        anyhow::ensure!(
+            output.status.success(),
+            "Exit code of external signer: {}",
+            output.status
+        );
+
+        public_key
+            .verify(msg, &output.stdout)
+            .context("External signer returned an invalid signature")?;
+
+        Ok(output.stdout)
     }
 }
 
@@ -97,6 +272,121 @@ impl PublicKey {
                 .context("Failed to compute ml-signify public key fingerprint"),
         }
     }
+
+    /// Compute the fingerprints under which signatures made with this key,
+    /// or with an equivalent key in another format, are stored.
+    pub fn equivalent_fingerprints(&self) -> Result<Vec<Oid>> {
+        let mut fingerprints = vec![self.fingerprint()?];
+
+        match self {
+            Self::Signify(_) => {
+                fingerprints.push(self.convert(TreeSignatureAlgo::Minisign)?.fingerprint()?);
+            }
+            Self::Minisign(_) => {
+                fingerprints.push(self.convert(TreeSignatureAlgo::Signify)?.fingerprint()?);
+            }
+            Self::MlSignify(_) => {}
+        }
+
+        Ok(fingerprints)
+    }
+
+    /// Identify this key regardless of its format, as the smallest of
+    /// its [equivalent fingerprints](Self::equivalent_fingerprints).
+    /// Keys with the same identity only count once towards a threshold.
+    pub fn identity(&self) -> Result<Oid> {
+        self.equivalent_fingerprints()?
+            .into_iter()
+            .min()
+            .context("Key has no fingerprint")
+    }
+
+    /// Convert this key to the format of the given algorithm. Only
+    /// conversions between signify and minisign keys are supported,
+    /// since both wrap Ed25519 keys with the same binary layout.
+    pub fn convert(&self, algorithm: TreeSignatureAlgo) -> Result<Self> {
+        match (self, algorithm) {
+            (Self::Signify(public_key), TreeSignatureAlgo::Minisign) => {
+                minisign::PublicKey::from_bytes(&public_key.as_bytes())
+                    .map(Self::Minisign)
+                    .context("Failed to convert signify public key to minisign")
+            }
+            (Self::Minisign(public_key), TreeSignatureAlgo::Signify) => {
+                libsignify::PublicKey::from_bytes(&public_key.to_bytes())
+                    .map(Self::Signify)
+                    .map_err(Error::new)
+                    .context("Failed to convert minisign public key to signify")
+            }
+            (public_key, algorithm) => anyhow::bail!(
+                "Cannot convert {} public key to {}",
+                public_key.algorithm().as_str(),
+                algorithm.as_str()
+            ),
+        }
+    }
+
+    /// Return the algorithm of this [`PublicKey`].
+    pub const fn algorithm(&self) -> TreeSignatureAlgo {
+        match self {
+            Self::Signify(_) => TreeSignatureAlgo::Signify,
+            Self::Minisign(_) => TreeSignatureAlgo::Minisign,
+            Self::MlSignify(_) => TreeSignatureAlgo::MlSignify,
+        }
+    }
+
+    /// Verify an encoded `signature` over `msg`, as returned by
+    /// [`PrivateKey::sign`].
+    pub fn verify<T: AsRef<[u8]>>(&self, msg: T, signature: &[u8]) -> Result<()> {
+        match self {
+            Self::Signify(public_key) => {
+                let signature_content = std::str::from_utf8(signature)
+                    .context("Found non-utf8 data in signify signature content")?;
+
+                let (signature, _) = libsignify::Signature::from_base64(signature_content)
+                    .map_err(Error::new)
+                    .context("Failed to parse signify signature")?;
+
+                public_key
+                    .verify(msg.as_ref(), &signature)
+                    .map_err(Error::new)
+                    .context("Invalid signify signature")
+            }
+            Self::Minisign(public_key) => {
+                let signature_content = std::str::from_utf8(signature)
+                    .context("Found non-utf8 data in minisign signature content")?;
+
+                let signature_box = minisign::SignatureBox::from_string(signature_content)
+                    .context("Failed to parse minisign signature")?;
+
+                minisign::verify(
+                    public_key,
+                    &signature_box,
+                    Cursor::new(msg.as_ref()),
+                    true,
+                    false,
+                    false,
+                )
+                .context("Invalid minisign signature")
+            }
+            Self::MlSignify(public_key) => {
+                let signature_content = std::str::from_utf8(signature)
+                    .context("Found non-utf8 data in ml-signify signature content")?;
+
+                let (_, signature) = signature_content
+                    .ml_signify_decode::<codecs::Signature>()
+                    .context("Failed to parse ml-signify signature")?;
+
+                let message = ml_signify::hash(msg.as_ref());
+
+                anyhow::ensure!(
+                    ml_signify::verify(public_key, &message, &signature),
+                    "Invalid ml-signify signature"
+                );
+
+                Ok(())
+            }
+        }
+    }
 }
 
 /// Enumeration of all possible versions of a [`TreeSignature`].
@@ -142,6 +432,7 @@ impl TreeSignatureVersion {
 }
 
 /// Enumeration of all possible algorithms of a [`TreeSignature`].
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TreeSignatureAlgo {
     /// Signify key.
     Signify,
@@ -166,7 +457,7 @@ impl TreeSignatureAlgo {
     }
 
     /// Encode the algorithm as a string.
-    pub const fn as_str(&self) -> &str {
+    pub const fn as_str(&self) -> &'static str {
         match self {
             Self::Signify => "signify",
             Self::Minisign => "minisign",
@@ -221,6 +512,20 @@ impl<'repo> TreeSignature<'repo> {
         .context("Failed to look-up tree signature")
     }
 
+    /// Like [`TreeSignature::load_oid`], but fails unless the signature
+    /// is over `signed_oid`, e.g. the object named by its reference.
+    pub fn load_oid_over(repo: &'repo Repository, oid: Oid, signed_oid: Oid) -> Result<Self> {
+        let tree_sig = Self::load_oid(repo, oid)?;
+
+        let dereferenced_obj = tree_sig.dereference()?;
+        anyhow::ensure!(
+            dereferenced_obj == signed_oid,
+            "Signature is over {dereferenced_obj}, rather than {signed_oid}"
+        );
+
+        Ok(tree_sig)
+    }
+
     /// Like [`TreeSignature::load`], but uses a concrete revision pointing
     /// to the tree signature.
     pub fn load_oid(repo: &'repo Repository, oid: Oid) -> Result<Self> {
@@ -353,95 +658,36 @@ impl<'repo> TreeSignature<'repo> {
 
     /// Verify the authenticity of this [`TreeSignature`].
     pub fn verify(&self, public_key: &PublicKey) -> Result<()> {
+        // signify and minisign keys are interchangeable, so signatures
+        // made with either format are verified with the equivalent key
+        let converted_key = match (&self.algorithm, public_key) {
+            (TreeSignatureAlgo::Signify, PublicKey::Minisign(_))
+            | (TreeSignatureAlgo::Minisign, PublicKey::Signify(_)) => {
+                Some(public_key.convert(self.algorithm)?)
+            }
+            _ => None,
+        };
+        let public_key = converted_key.as_ref().unwrap_or(public_key);
+
         self.check_compatibility(public_key)
             .context("Incompatible public key provided")?;
 
-        match public_key {
-            PublicKey::Signify(public_key) => {
-                let signature = match &self.version {
-                    TreeSignatureVersion::V0 => {
-                        libsignify::Signature::from_bytes(self.signature.content())
-                            .map_err(Error::new)
-                            .context("Failed to parse signify signature from git blob")?
-                    }
-                    TreeSignatureVersion::V1
-                    | TreeSignatureVersion::V2
-                    | TreeSignatureVersion::V3 => {
-                        let signature_content = std::str::from_utf8(self.signature.content())
-                            .context("Found non-utf8 data in signify signature content")?;
-
-                        let (signature, _) = libsignify::Signature::from_base64(signature_content)
-                            .map_err(Error::new)
-                            .context("Failed to parse signify signature from git blob")?;
-
-                        signature
-                    }
-                };
+        let dereferenced_obj = self.dereference()?;
 
-                let dereferenced_obj = self.dereference()?;
+        match (&self.version, public_key) {
+            (TreeSignatureVersion::V0, PublicKey::Signify(public_key)) => {
+                let signature = libsignify::Signature::from_bytes(self.signature.content())
+                    .map_err(Error::new)
+                    .context("Failed to parse signify signature from git blob")?;
 
                 public_key
                     .verify(dereferenced_obj.as_bytes(), &signature)
                     .map_err(Error::new)
                     .context("Invalid signify signature")
             }
-            PublicKey::Minisign(public_key) => {
-                let signature_box = match &self.version {
-                    TreeSignatureVersion::V0 => {
-                        anyhow::bail!("minisign public keys not supported in v0");
-                    }
-                    TreeSignatureVersion::V1
-                    | TreeSignatureVersion::V2
-                    | TreeSignatureVersion::V3 => {
-                        let signature_content = std::str::from_utf8(self.signature.content())
-                            .context("Found non-utf8 data in minisign signature content")?;
-
-                        minisign::SignatureBox::from_string(signature_content)
-                            .context("Failed to parse minisign signature from git blob")?
-                    }
-                };
-
-                let dereferenced_obj = self.dereference()?;
-
-                minisign::verify(
-                    public_key,
-                    &signature_box,
-                    Cursor::new(dereferenced_obj.as_bytes()),
-                    true,
-                    false,
-                    false,
-                )
-                .context("Invalid minisign signature")
-            }
-            PublicKey::MlSignify(public_key) => {
-                let signature = match &self.version {
-                    TreeSignatureVersion::V0
-                    | TreeSignatureVersion::V1
-                    | TreeSignatureVersion::V2 => {
-                        anyhow::bail!("ml-signify public keys are only supported from v3 onwards");
-                    }
-                    TreeSignatureVersion::V3 => {
-                        let signature_content = std::str::from_utf8(self.signature.content())
-                            .context("Found non-utf8 data in ml-signify signature content")?;
-
-                        let (_, signature) = signature_content
-                            .ml_signify_decode::<codecs::Signature>()
-                            .context("Failed to parse ml-signify signature from git blob")?;
-
-                        signature
-                    }
-                };
-
-                let dereferenced_obj = self.dereference()?;
-                let message = ml_signify::hash(dereferenced_obj.as_bytes());
-
-                anyhow::ensure!(
-                    ml_signify::verify(public_key, &message, &signature),
-                    "Invalid ml-signify signature"
-                );
-
-                Ok(())
-            }
+            _ => public_key
+                .verify(dereferenced_obj.as_bytes(), self.signature.content())
+                .context("Failed to verify signature from git blob"),
         }
     }
 
@@ -515,6 +761,9 @@ fn hash_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Oid> {
     Oid::hash_object(ObjectType::Blob, bytes.as_ref()).context("Failed to hash bytes")
 }
 
+/// Comment identifying key files that configure an [`ExternalSigner`].
+const EXTERNAL_SIGNER_COMMENT: &str = "untrusted comment: external signer";
+
 /// Determine the format of the given key data.
 fn determine_key_format(key_data: &str) -> Result<TreeSignatureAlgo> {
     const UNTRUSTED_COMMENT: &str = "untrusted comment: ";
@@ -554,6 +803,88 @@ where
     Ok(keys)
 }
 
+/// Public keys indexed by the fingerprints under which their
+/// signatures are stored.
+pub struct Keyring {
+    /// The keys in this keyring, along with their paths.
+    keys: Vec<(PathBuf, PublicKey)>,
+    /// Index of [`Keyring::keys`] by fingerprint.
+    by_fingerprint: BTreeMap<Oid, usize>,
+}
+
+impl Keyring {
+    /// Read a [`Keyring`] from the given path, with the same
+    /// semantics as [`get_public_keys`].
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let keys: Vec<_> = get_public_keys(path)?.into_iter().collect();
+        let mut by_fingerprint = BTreeMap::new();
+
+        for (index, (_, public_key)) in keys.iter().enumerate() {
+            for fingerprint in public_key.equivalent_fingerprints()? {
+                by_fingerprint.insert(fingerprint, index);
+            }
+        }
+
+        Ok(Self {
+            keys,
+            by_fingerprint,
+        })
+    }
+
+    /// Look-up a key by fingerprint.
+    pub fn get(&self, fingerprint: Oid) -> Option<(&Path, &PublicKey)> {
+        let (path, public_key) = &self.keys[*self.by_fingerprint.get(&fingerprint)?];
+        Some((path, public_key))
+    }
+}
+
+/// Outcome of checking a signature reference with [`check_signature`].
+pub enum SignatureStatus<'keyring> {
+    /// The signature verifies under the key named by the reference.
+    Valid {
+        /// Path of the key that verified the signature.
+        key_path: &'keyring Path,
+    },
+    /// No key with the fingerprint named by the reference is known.
+    UnknownKey,
+    /// The signature is invalid.
+    Invalid(anyhow::Error),
+}
+
+/// Check the signature commit `signature_oid`, stored under a reference
+/// naming the fingerprint `signer` and the signed object `signed_oid`.
+/// The signature must verify under the key with that fingerprint, and
+/// sign over that same object.
+pub fn check_signature<'keyring>(
+    repo: &Repository,
+    keyring: &'keyring Keyring,
+    signer: Oid,
+    signed_oid: Oid,
+    signature_oid: Oid,
+) -> SignatureStatus<'keyring> {
+    let Some((key_path, public_key)) = keyring.get(signer) else {
+        return SignatureStatus::UnknownKey;
+    };
+
+    match verify_signature_over(repo, public_key, signature_oid, signed_oid) {
+        Ok(()) => SignatureStatus::Valid { key_path },
+        Err(e) => SignatureStatus::Invalid(e),
+    }
+}
+
+/// Verify the signature commit `signature_oid` with `public_key`, and
+/// check that it signs over `signed_oid`. Since the names of signature
+/// references are not authenticated, this must be checked before
+/// trusting a signature found under a reference.
+pub fn verify_signature_over(
+    repo: &Repository,
+    public_key: &PublicKey,
+    signature_oid: Oid,
+    signed_oid: Oid,
+) -> Result<()> {
+    TreeSignature::load_oid_over(repo, signature_oid, signed_oid)?.verify(public_key)
+}
+
 /// Read public keys from the given path. If a directory is provided,
 /// keys are read from files whose extension is `.pub`.
 pub fn get_public_keys(path: PathBuf) -> Result<BTreeMap<PathBuf, PublicKey>> {
@@ -620,37 +951,55 @@ pub fn get_secret_keys(path: PathBuf) -> Result<BTreeMap<PathBuf, PrivateKey>> {
 
 /// Read a secret key from the given path.
 fn get_secret_key(path: &Path) -> Result<PrivateKey> {
+    read_secret_key(path).map(|(secret_key, _)| secret_key)
+}
+
+/// Read a secret key from the given path, along with the passphrase it
+/// is encrypted with, if any.
+pub fn read_secret_key(path: &Path) -> Result<(PrivateKey, Option<Zeroizing<String>>)> {
     let key_data = std::fs::read_to_string(path)
         .map(Zeroizing::new)
         .context("Failed to read secret key")?;
 
+    if key_data.starts_with(EXTERNAL_SIGNER_COMMENT) {
+        return ExternalSigner::from_key_data(path, &key_data)
+            .map(|signer| (PrivateKey::External(signer), None));
+    }
+
     Ok(match determine_key_format(&key_data)? {
         TreeSignatureAlgo::Signify => {
             let (mut secret_key, _) = libsignify::PrivateKey::from_base64(&key_data[..])
                 .map_err(Error::new)
                 .context("Failed to decode secret key")?;
 
-            if secret_key.is_encrypted() {
+            let passphrase = if secret_key.is_encrypted() {
                 let passphrase = prompt_key_passphrase(path).map(Zeroizing::new)?;
 
                 secret_key
                     .decrypt_with_password(&passphrase)
                     .map_err(Error::new)
                     .context("Failed to decrypt secret key")?;
-            }
 
-            PrivateKey::Signify(secret_key)
+                Some(passphrase)
+            } else {
+                None
+            };
+
+            (PrivateKey::Signify(secret_key), passphrase)
         }
         TreeSignatureAlgo::Minisign => {
             let private_key = minisign::SecretKeyBox::from_string(&key_data[..])
                 .context("Failed to read minisign secret key")?;
 
-            let passphrase = prompt_key_passphrase(path)?;
+            let passphrase = prompt_key_passphrase(path).map(Zeroizing::new)?;
 
-            PrivateKey::Minisign(
-                private_key
-                    .into_secret_key(Some(passphrase))
-                    .context("Failed to decode minisign private key")?,
+            (
+                PrivateKey::Minisign(
+                    private_key
+                        .into_secret_key(Some(String::clone(&passphrase)))
+                        .context("Failed to decode minisign private key")?,
+                ),
+                Some(passphrase),
             )
         }
         TreeSignatureAlgo::MlSignify => {
@@ -658,30 +1007,374 @@ fn get_secret_key(path: &Path) -> Result<PrivateKey> {
                 .ml_signify_decode::<codecs::SigningKey>()
                 .context("Failed to decode ml-signify sealed secret key")?;
 
-            let passphrase = prompt_key_passphrase(path)?;
+            let passphrase = prompt_key_passphrase(path).map(Zeroizing::new)?;
 
             let private_key =
                 ml_signify::seal::unseal_signing_key(&sealed_key, passphrase.as_bytes())
                     .context("Failed to unseal ml-signify secret key")?;
 
-            PrivateKey::MlSignify(Box::new(private_key.signing_key().clone()))
+            (
+                PrivateKey::MlSignify(Box::new(private_key.signing_key().clone())),
+                Some(passphrase),
+            )
         }
     })
 }
 
+/// Encode a public key in the file format of its algorithm.
+pub fn encode_public_key(public_key: &PublicKey) -> Result<String> {
+    match public_key {
+        PublicKey::Signify(public_key) => {
+            String::from_utf8(public_key.to_file_encoding("signify public key"))
+                .context("Found non-utf8 data in encoded signify public key")
+        }
+        PublicKey::Minisign(public_key) => public_key
+            .to_box()
+            .map(String::from)
+            .context("Failed to encode minisign public key"),
+        PublicKey::MlSignify(public_key) => {
+            let key_id = ml_signify::id_from_verifying_key(public_key);
+
+            Ok((key_id, &**public_key)
+                .ml_signify_encode::<codecs::VerifyingKey>(Some("ml-signify public key")))
+        }
+    }
+}
+
+/// Encode a secret key in the file format of its algorithm. The key
+/// is encrypted with `passphrase`, if one is provided.
+pub fn encode_secret_key(
+    secret_key: &PrivateKey,
+    passphrase: Option<&str>,
+) -> Result<Zeroizing<String>> {
+    match secret_key {
+        PrivateKey::Signify(secret_key) => {
+            let mut bytes = Zeroizing::new(secret_key.as_bytes());
+
+            let checksum = signify_checksum(&bytes[SIGNIFY_SECRET_KEY]);
+            bytes[SIGNIFY_SECRET_CHECKSUM].copy_from_slice(&checksum);
+
+            if let Some(passphrase) = passphrase {
+                getrandom::getrandom(&mut bytes[SIGNIFY_SECRET_SALT])
+                    .map_err(Error::new)
+                    .context("Failed to generate signify secret key salt")?;
+                bytes[SIGNIFY_SECRET_KDF_ROUNDS].copy_from_slice(&SIGNIFY_KDF_ROUNDS.to_be_bytes());
+
+                let mut xor_key = Zeroizing::new([0u8; 64]);
+                bcrypt_pbkdf::bcrypt_pbkdf(
+                    passphrase,
+                    &bytes[SIGNIFY_SECRET_SALT],
+                    SIGNIFY_KDF_ROUNDS,
+                    &mut xor_key[..],
+                )
+                .map_err(Error::new)
+                .context("Failed to derive signify secret key encryption key")?;
+
+                xor_in_place(&mut bytes[SIGNIFY_SECRET_KEY], &xor_key[..]);
+            } else {
+                bytes[SIGNIFY_SECRET_KDF_ROUNDS].copy_from_slice(&0u32.to_be_bytes());
+                bytes[SIGNIFY_SECRET_SALT].fill(0);
+            }
+
+            let secret_key = libsignify::PrivateKey::from_bytes(&bytes)
+                .map_err(Error::new)
+                .context("Failed to encode signify secret key")?;
+
+            String::from_utf8(secret_key.to_file_encoding("signify secret key"))
+                .map(Zeroizing::new)
+                .context("Found non-utf8 data in encoded signify secret key")
+        }
+        PrivateKey::Minisign(secret_key) => {
+            let mut bytes = Zeroizing::new(secret_key.to_bytes());
+
+            let comment = if let Some(passphrase) = passphrase {
+                bytes[2..4].copy_from_slice(b"Sc");
+                getrandom::getrandom(&mut bytes[MINISIGN_SECRET_SALT])
+                    .map_err(Error::new)
+                    .context("Failed to generate minisign secret key salt")?;
+                bytes[MINISIGN_SECRET_KDF_OPSLIMIT]
+                    .copy_from_slice(&MINISIGN_KDF_OPSLIMIT.to_le_bytes());
+                bytes[MINISIGN_SECRET_KDF_MEMLIMIT]
+                    .copy_from_slice(&MINISIGN_KDF_MEMLIMIT.to_le_bytes());
+
+                let mut xor_key = Zeroizing::new(vec![0u8; MINISIGN_SECRET_ENCRYPTED.len()]);
+                minisign_kdf(
+                    passphrase,
+                    &bytes[MINISIGN_SECRET_SALT],
+                    MINISIGN_KDF_OPSLIMIT,
+                    MINISIGN_KDF_MEMLIMIT,
+                    &mut xor_key,
+                )?;
+
+                xor_in_place(&mut bytes[MINISIGN_SECRET_ENCRYPTED], &xor_key);
+
+                "minisign encrypted secret key"
+            } else {
+                bytes[2..4].copy_from_slice(&[0, 0]);
+                bytes[MINISIGN_SECRET_SALT].fill(0);
+                bytes[MINISIGN_SECRET_KDF_OPSLIMIT].fill(0);
+                bytes[MINISIGN_SECRET_KDF_MEMLIMIT].fill(0);
+
+                "minisign secret key"
+            };
+
+            minisign::SecretKey::from_bytes(&bytes)
+                .and_then(|secret_key| secret_key.to_box(Some(comment)))
+                .map(|secret_key_box| Zeroizing::new(String::from(secret_key_box)))
+                .context("Failed to encode minisign secret key")
+        }
+        PrivateKey::MlSignify(secret_key) => {
+            let passphrase =
+                passphrase.context("ml-signify secret keys must be encrypted with a passphrase")?;
+
+            let sealed_key = ml_signify::seal::seal_signing_key(secret_key, passphrase.as_bytes())
+                .context("Failed to seal ml-signify secret key")?;
+
+            Ok(Zeroizing::new(
+                sealed_key.ml_signify_encode::<codecs::SigningKey>(Some("ml-signify secret key")),
+            ))
+        }
+        PrivateKey::External(_) => {
+            anyhow::bail!("The secret key of an external signer cannot be encoded")
+        }
+    }
+}
+
+/// Number of bcrypt rounds used to encrypt signify secret keys, like
+/// `signify -G` does.
+const SIGNIFY_KDF_ROUNDS: u32 = 42;
+
+/// Byte length of a binary signify secret key.
+const SIGNIFY_SECRET_KEY_LEN: usize = 104;
+
+/// Byte range of the bcrypt rounds in a binary signify secret key.
+const SIGNIFY_SECRET_KDF_ROUNDS: std::ops::Range<usize> = 4..8;
+
+/// Byte range of the bcrypt salt in a binary signify secret key.
+const SIGNIFY_SECRET_SALT: std::ops::Range<usize> = 8..24;
+
+/// Byte range of the checksum in a binary signify secret key.
+const SIGNIFY_SECRET_CHECKSUM: std::ops::Range<usize> = 24..32;
+
+/// Byte range of the key number in a binary signify secret key.
+const SIGNIFY_SECRET_KEYNUM: std::ops::Range<usize> = 32..40;
+
+/// Byte range of the Ed25519 key in a binary signify secret key.
+const SIGNIFY_SECRET_KEY: std::ops::Range<usize> = 40..104;
+
+/// scrypt ops limit used to encrypt minisign secret keys, like the
+/// [`minisign`] crate does, rather than the C `minisign -G` and its
+/// libsodium `OPSLIMIT_SENSITIVE`.
+const MINISIGN_KDF_OPSLIMIT: u64 = 1_048_576;
+
+/// scrypt memory limit used to encrypt minisign secret keys, like the
+/// [`minisign`] crate does, rather than the C `minisign -G` and its
+/// libsodium `MEMLIMIT_SENSITIVE`.
+const MINISIGN_KDF_MEMLIMIT: u64 = 33_554_432;
+
+/// Byte length of a binary minisign secret key.
+const MINISIGN_SECRET_KEY_LEN: usize = 158;
+
+/// Byte range of the scrypt salt in a binary minisign secret key.
+const MINISIGN_SECRET_SALT: std::ops::Range<usize> = 6..38;
+
+/// Byte range of the scrypt ops limit in a binary minisign secret key.
+const MINISIGN_SECRET_KDF_OPSLIMIT: std::ops::Range<usize> = 38..46;
+
+/// Byte range of the scrypt memory limit in a binary minisign secret key.
+const MINISIGN_SECRET_KDF_MEMLIMIT: std::ops::Range<usize> = 46..54;
+
+/// Byte range of the encrypted part of a binary minisign secret key.
+const MINISIGN_SECRET_ENCRYPTED: std::ops::Range<usize> = 54..158;
+
+/// Byte range of the key number in a binary minisign secret key.
+const MINISIGN_SECRET_KEYNUM: std::ops::Range<usize> = 54..62;
+
+/// Byte range of the Ed25519 key in a binary minisign secret key.
+const MINISIGN_SECRET_KEY: std::ops::Range<usize> = 62..126;
+
+/// Byte range of the checksum in a binary minisign secret key.
+const MINISIGN_SECRET_CHECKSUM: std::ops::Range<usize> = 126..158;
+
+/// Compute the checksum of a signify secret key.
+fn signify_checksum(secret_key: &[u8]) -> [u8; 8] {
+    use sha2::Digest as _;
+
+    let digest = sha2::Sha512::digest(secret_key);
+    let mut checksum = [0u8; 8];
+    checksum.copy_from_slice(&digest[..8]);
+    checksum
+}
+
+/// Derive the key used to encrypt minisign secret keys, picking scrypt
+/// parameters from the given limits like libsodium does.
+fn minisign_kdf(
+    passphrase: &str,
+    salt: &[u8],
+    opslimit: u64,
+    memlimit: u64,
+    output: &mut [u8],
+) -> Result<()> {
+    let opslimit = opslimit.max(32768);
+    let r = 8u32;
+    let mut n_log2 = 1u8;
+
+    let p = if opslimit < memlimit / 32 {
+        let max_n = opslimit / (u64::from(r) * 4);
+        while n_log2 < 63 && 1u64 << n_log2 <= max_n / 2 {
+            n_log2 += 1;
+        }
+        1
+    } else {
+        let max_n = memlimit / (u64::from(r) * 128);
+        while n_log2 < 63 && 1u64 << n_log2 <= max_n / 2 {
+            n_log2 += 1;
+        }
+        let max_rp = ((opslimit / 4) / (1u64 << n_log2)).min(0x3fff_ffff) as u32;
+        max_rp / r
+    };
+
+    // the key length of the parameters only applies to password hashes,
+    // and may not exceed 64 bytes, while minisign derives 104 bytes
+    let params = scrypt::Params::new(n_log2, r, p, scrypt::Params::RECOMMENDED_LEN)
+        .map_err(Error::new)
+        .context("Invalid minisign scrypt parameters")?;
+
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, output)
+        .map_err(Error::new)
+        .context("Failed to derive minisign secret key encryption key")
+}
+
+/// XOR `key` into `data`.
+fn xor_in_place(data: &mut [u8], key: &[u8]) {
+    for (d, k) in data.iter_mut().zip(key) {
+        *d ^= k;
+    }
+}
+
+/// Atomically write `contents` to the file at `path`, by writing to
+/// a uniquely named temporary file in the same directory and renaming
+/// it over `path`. Secret files are only made accessible to their owner,
+/// others keep the permissions of the file they replace.
+pub fn write_file_atomically(path: &Path, contents: &[u8], secret: bool) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid file path {}", path.display()))?;
+    let mut suffix = [0; 8];
+    getrandom::getrandom(&mut suffix).context("Failed to generate temporary file name")?;
+    let suffix = u64::from_ne_bytes(suffix);
+    let tmp_path = path.with_file_name({
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(file_name);
+        tmp_name.push(format!(".{}.{suffix:016x}.tmp", std::process::id()));
+        tmp_name
+    });
+    let permissions = fs::metadata(path).ok().map(|meta| meta.permissions());
+
+    let write_tmp = || -> Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        if secret {
+            use std::os::unix::fs::OpenOptionsExt as _;
+            options.mode(0o600);
+        }
+
+        let mut file = options
+            .open(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        if let Some(permissions) = permissions.filter(|_| !secret) {
+            file.set_permissions(permissions)
+                .with_context(|| format!("Failed to set permissions of {}", tmp_path.display()))?;
+        }
+        file.write_all(contents)
+            .and_then(|()| file.sync_all())
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to move {} into place", tmp_path.display()))
+    };
+
+    write_tmp().inspect_err(|_| {
+        _ = fs::remove_file(&tmp_path);
+    })?;
+
+    // persist the rename itself, which is an update of the directory
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Failed to sync directory {}", dir.display()))?;
+    }
+
+    Ok(())
+}
+
 fn prompt_key_passphrase(path: &Path) -> Result<String> {
     rpassword::prompt_password(format!("{} passphrase: ", path.display()))
         .context("Failed to read secret key passphrase")
 }
 
-/// Try to find and open a git repository.
+/// Try to find and open a git repository, starting from the current
+/// directory, and honoring `GIT_DIR` and related environment variables
+/// like git does. Bare repositories are supported.
 pub fn open_repository() -> Result<Repository> {
-    Repository::open_ext(
-        ".",
-        RepositoryOpenFlags::empty(),
-        &[] as &[&std::ffi::OsStr],
-    )
-    .context("Failed to open git repository")
+    Repository::open_from_env().context("Failed to open git repository")
+}
+
+/// Collect the names of the references matching `glob`, other than
+/// those created by `git-signify`, sorted by name.
+pub fn matching_refs(repo: &Repository, glob: &str) -> Result<Vec<String>> {
+    let mut refnames = Vec::new();
+
+    for maybe_ref in repo
+        .references_glob(glob)
+        .with_context(|| format!("Failed to look-up refs matching {glob}"))?
+    {
+        let reference = maybe_ref.context("Failed to parse git reference")?;
+        let refname = reference.name().context("Invalid reference name")?;
+        if !refname.starts_with("refs/signify/") {
+            refnames.push(refname.to_owned());
+        }
+    }
+
+    refnames.sort();
+    Ok(refnames)
+}
+
+/// Create the reflog of `refname`, and configure `git gc` to never
+/// expire its entries, which are kept for audit. The config is only
+/// written when the reflog is first created, settings which are already
+/// present are left alone, and the settings written are reported.
+pub fn keep_reflog(repo: &Repository, refname: &str) -> Result<()> {
+    if repo.reference_has_log(refname).unwrap_or(false) {
+        return Ok(());
+    }
+
+    let mut config = repo.config().context("Failed to open git config")?;
+    for key in ["reflogExpire", "reflogExpireUnreachable"] {
+        let key = format!("gc.{refname}.{key}");
+        if config.get_string(&key).is_ok() {
+            continue;
+        }
+        // e.g. read-only configs, which should not prevent signing
+        match config.set_str(&key, "never") {
+            Ok(()) => {
+                eprintln!("Set {key} to never in git config, to keep the reflog of {refname}")
+            }
+            Err(e) => eprintln!(
+                "warning: failed to set {key} in git config, `git gc` may expire the \
+                 reflog of {refname}: {e}"
+            ),
+        }
+    }
+
+    repo.reference_ensure_log(refname)
+        .with_context(|| format!("Failed to create the reflog of {refname}"))
 }
 
 /// Craft a git reference to an object signed by a key with the given
@@ -690,11 +1383,363 @@ pub fn craft_signature_reference(key_fingerprint: Oid, signed_object: Oid) -> St
     format!("refs/signify/signatures/{key_fingerprint}/{signed_object}")
 }
 
+/// Craft a git reference to a countersignature by the key with the
+/// fingerprint `witness`, over the signature made by `signer` over
+/// `signed_object`.
+pub fn craft_countersignature_reference(signer: Oid, signed_object: Oid, witness: Oid) -> String {
+    format!("{ALL_SIGNIFY_COUNTERSIGNATURE_REFS_PREFIX}{signer}/{signed_object}/{witness}")
+}
+
+/// Parse the signer fingerprint and the signed object id out of the
+/// `<fingerprint>/<oid>` suffix of a signature reference. Both must be
+/// spelled out in full in lowercase hex, as git-signify names references,
+/// such that each signature has a single reference name.
+pub fn parse_signer_and_oid(signer_and_oid: &str) -> Option<(Oid, Oid)> {
+    let (signer, oid) = signer_and_oid.split_once('/')?;
+
+    let parse = |hex: &str| {
+        let canonical =
+            hex.len() == 40 && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+        Oid::from_str(hex).ok().filter(|_| canonical)
+    };
+
+    Some((parse(signer)?, parse(oid)?))
+}
+
+/// Git reference prefix under which signatures fetched from the remote
+/// `remote` are stored, before being verified.
+pub fn remote_signature_refs_prefix(remote: &str) -> String {
+    format!("{SIGNIFY_REMOTES_REFS_PREFIX}{remote}/signatures/")
+}
+
+/// Match `name` against a shell-like `glob`, where `*` matches any
+/// sequence of characters (including `/`) and `?` any one character,
+/// like the globs of [`Repository::references_glob`].
+pub fn glob_matches(glob: &str, name: &str) -> bool {
+    let (glob, name) = (glob.as_bytes(), name.as_bytes());
+    let (mut g, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match glob.get(g) {
+            Some(b'*') => {
+                backtrack = Some((g, n));
+                g += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => {
+                let Some((star_g, star_n)) = backtrack else {
+                    return false;
+                };
+                backtrack = Some((star_g, star_n + 1));
+                g = star_g + 1;
+                n = star_n + 1;
+            }
+        }
+    }
+
+    glob[g..].iter().all(|&c| c == b'*')
+}
+
 /// Git refspec describing all signify references.
 pub const ALL_SIGNIFY_REFS: &str = "refs/signify/*";
 
+/// Git refspec prefix describing signify references fetched from
+/// remotes, which have yet to be verified.
+pub const SIGNIFY_REMOTES_REFS_PREFIX: &str = "refs/signify/remotes/";
+
 /// Git refspec describing all signify signature references.
 pub const ALL_SIGNIFY_SIGNATURE_REFS: &str = "refs/signify/signatures/*";
 
 /// Git refspec prefix describing all signify signature references.
 pub const ALL_SIGNIFY_SIGNATURE_REFS_PREFIX: &str = "refs/signify/signatures/";
+
+/// Git refspec prefix describing all signify attestation references.
+pub const ALL_SIGNIFY_ATTESTATION_REFS_PREFIX: &str = "refs/signify/attestations/";
+
+/// Git refspec prefix describing all signify countersignature references.
+pub const ALL_SIGNIFY_COUNTERSIGNATURE_REFS_PREFIX: &str = "refs/signify/countersignatures/";
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{env, process};
+
+    use git2::{Commit, Signature};
+
+    use super::*;
+
+    /// Unencrypted signify secret key, wrapping the Ed25519 key of the
+    /// first test vector of RFC 8032, with key number `0102030405060708`.
+    const SIGNIFY_SECRET_KEY_FIXTURE: &str = "untrusted comment: signify secret key
+RWRCSwAAAAAAAAAAAAAAAAAAAAAAAAAASulvySzYtIABAgMEBQYHCJ1hsZ3v/VpguoRK9JLsLMREScVpezJpGXA7rAMcrn9g11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=
+";
+
+    /// [`SIGNIFY_SECRET_KEY_FIXTURE`] as an unencrypted minisign secret key.
+    const MINISIGN_SECRET_KEY_FIXTURE: &str = "untrusted comment: minisign secret key
+RWQAAEIyAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQIDBAUGBwidYbGd7/1aYLqESvSS7CzEREnFaXsyaRlwO6wDHK5/YNdamAGCsQq31Uv+08lkBzoO4XLz2qYjJa8CGmj3B1Easnti+28UCxR4s44CIMHgVQLxV3yjCRorwu2dsQ2VcXg=
+";
+
+    const MESSAGE: &[u8] = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n";
+
+    pub(crate) fn signify_fixture() -> PrivateKey {
+        let (secret_key, _) = libsignify::PrivateKey::from_base64(SIGNIFY_SECRET_KEY_FIXTURE)
+            .expect("signify fixture should decode");
+        PrivateKey::Signify(secret_key)
+    }
+
+    pub(crate) fn minisign_fixture() -> PrivateKey {
+        let secret_key = minisign::SecretKeyBox::from_string(MINISIGN_SECRET_KEY_FIXTURE)
+            .and_then(|secret_key| secret_key.into_secret_key(Some(String::new())))
+            .expect("minisign fixture should decode");
+        PrivateKey::Minisign(secret_key)
+    }
+
+    /// Create an empty repository named after the test.
+    pub(crate) fn test_repository(name: &str) -> Repository {
+        let path = env::temp_dir().join(format!("git-signify-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&path);
+        Repository::init(path).unwrap()
+    }
+
+    /// Create a commit with an empty tree over `parents`.
+    pub(crate) fn test_commit(repo: &Repository, message: &str, parents: &[&Commit<'_>]) -> Oid {
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap());
+        repo.commit(
+            None,
+            &signature,
+            &signature,
+            message,
+            &tree.unwrap(),
+            parents,
+        )
+        .unwrap()
+    }
+
+    /// Reload an encoded signify secret key with [`libsignify`].
+    fn reload_signify(encoded: &str, passphrase: &str) -> Result<PrivateKey> {
+        let (mut secret_key, _) =
+            libsignify::PrivateKey::from_base64(encoded).map_err(Error::new)?;
+        secret_key
+            .decrypt_with_password(passphrase)
+            .map_err(Error::new)?;
+        Ok(PrivateKey::Signify(secret_key))
+    }
+
+    /// Reload an encoded minisign secret key with [`minisign`].
+    fn reload_minisign(encoded: &str, passphrase: &str) -> Result<PrivateKey> {
+        let secret_key = minisign::SecretKeyBox::from_string(encoded)?
+            .into_secret_key(Some(passphrase.to_owned()))?;
+        Ok(PrivateKey::Minisign(secret_key))
+    }
+
+    /// Check that `secret_key` signs messages verified by `public_key`.
+    fn assert_signs_for(secret_key: &PrivateKey, public_key: &PublicKey) {
+        let signature = secret_key.sign(MESSAGE).expect("signing should succeed");
+        public_key
+            .verify(MESSAGE, &signature)
+            .expect("signature should verify");
+        assert!(public_key.verify(b"other message", &signature).is_err());
+    }
+
+    #[test]
+    fn signify_to_minisign_matches_fixture() {
+        let converted = signify_fixture()
+            .convert(TreeSignatureAlgo::Minisign)
+            .unwrap();
+        let encoded = encode_secret_key(&converted, None).unwrap();
+        assert_eq!(&*encoded, MINISIGN_SECRET_KEY_FIXTURE);
+    }
+
+    #[test]
+    fn minisign_to_signify_matches_fixture() {
+        let converted = minisign_fixture()
+            .convert(TreeSignatureAlgo::Signify)
+            .unwrap();
+        let encoded = encode_secret_key(&converted, None).unwrap();
+        assert_eq!(&*encoded, SIGNIFY_SECRET_KEY_FIXTURE);
+    }
+
+    #[test]
+    fn unencrypted_keys_reencode_to_fixtures() {
+        let encoded = encode_secret_key(&signify_fixture(), None).unwrap();
+        assert_eq!(&*encoded, SIGNIFY_SECRET_KEY_FIXTURE);
+        let encoded = encode_secret_key(&minisign_fixture(), None).unwrap();
+        assert_eq!(&*encoded, MINISIGN_SECRET_KEY_FIXTURE);
+    }
+
+    #[test]
+    fn converted_public_keys_match() {
+        let signify = signify_fixture().public_key().unwrap();
+        let minisign = minisign_fixture().public_key().unwrap();
+
+        let converted = signify.convert(TreeSignatureAlgo::Minisign).unwrap();
+        assert_eq!(
+            converted.fingerprint().unwrap(),
+            minisign.fingerprint().unwrap()
+        );
+        let converted = minisign.convert(TreeSignatureAlgo::Signify).unwrap();
+        assert_eq!(
+            converted.fingerprint().unwrap(),
+            signify.fingerprint().unwrap()
+        );
+    }
+
+    #[test]
+    fn signify_to_minisign_round_trip() {
+        let public_key = signify_fixture()
+            .public_key()
+            .unwrap()
+            .convert(TreeSignatureAlgo::Minisign)
+            .unwrap();
+        let converted = signify_fixture()
+            .convert(TreeSignatureAlgo::Minisign)
+            .unwrap();
+
+        let encoded = encode_secret_key(&converted, Some("first")).unwrap();
+        assert!(reload_minisign(&encoded, "wrong").is_err());
+        let reloaded = reload_minisign(&encoded, "first").unwrap();
+        assert_signs_for(&reloaded, &public_key);
+
+        // like `key passwd`
+        let encoded = encode_secret_key(&reloaded, Some("second")).unwrap();
+        assert!(reload_minisign(&encoded, "first").is_err());
+        let reloaded = reload_minisign(&encoded, "second").unwrap();
+        assert_signs_for(&reloaded, &public_key);
+    }
+
+    #[test]
+    fn minisign_to_signify_round_trip() {
+        let public_key = minisign_fixture()
+            .public_key()
+            .unwrap()
+            .convert(TreeSignatureAlgo::Signify)
+            .unwrap();
+        let converted = minisign_fixture()
+            .convert(TreeSignatureAlgo::Signify)
+            .unwrap();
+
+        let encoded = encode_secret_key(&converted, Some("first")).unwrap();
+        assert!(reload_signify(&encoded, "wrong").is_err());
+        let reloaded = reload_signify(&encoded, "first").unwrap();
+        assert_signs_for(&reloaded, &public_key);
+
+        // like `key passwd`
+        let encoded = encode_secret_key(&reloaded, Some("second")).unwrap();
+        assert!(reload_signify(&encoded, "first").is_err());
+        let reloaded = reload_signify(&encoded, "second").unwrap();
+        assert_signs_for(&reloaded, &public_key);
+    }
+
+    #[test]
+    fn encrypted_keys_round_trip() {
+        for (secret_key, reload, fixture) in [
+            (
+                signify_fixture(),
+                reload_signify as fn(&str, &str) -> Result<PrivateKey>,
+                SIGNIFY_SECRET_KEY_FIXTURE,
+            ),
+            (
+                minisign_fixture(),
+                reload_minisign,
+                MINISIGN_SECRET_KEY_FIXTURE,
+            ),
+        ] {
+            let public_key = secret_key.public_key().unwrap();
+            let encoded = encode_secret_key(&secret_key, Some("passphrase")).unwrap();
+            assert_ne!(&*encoded, fixture);
+            let reloaded = reload(&encoded, "passphrase").unwrap();
+            assert_signs_for(&reloaded, &public_key);
+
+            // removing the passphrase drops the salt and KDF parameters
+            let encoded = encode_secret_key(&reloaded, None).unwrap();
+            assert_eq!(&*encoded, fixture);
+        }
+    }
+
+    #[test]
+    fn minisign_kdf_matches_scrypt() {
+        // scrypt(N = 2^15, r = 8, p = 1), as picked by libsodium for the
+        // limits of the minisign crate
+        let mut output = [0u8; 104];
+        minisign_kdf(
+            "correct horse",
+            &[7; 32],
+            MINISIGN_KDF_OPSLIMIT,
+            MINISIGN_KDF_MEMLIMIT,
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(
+            output
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>(),
+            "07d3314a8884190f3e70d334990e1cd17009d76c6a64f47a8251ad67b0df38d6\
+             a62713f0787549dcb936cd5dd95ea19c9081212aba5e22f41259417445b9805b\
+             fd0952a4c464178d7b56c5e944a54e8c0a8b29856fe138cb25951e1fd44525d3\
+             c0df037be462ca31"
+        );
+
+        // scrypt(N = 2^10, r = 8, p = 1), bound by the ops limit
+        minisign_kdf("correct horse", &[7; 32], 32768, 33_554_432, &mut output).unwrap();
+        assert_eq!(
+            output
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>(),
+            "603af72676ab229919faa2457dee3e510bb445b9a0cfa86833b7d05f6f75cb03\
+             c3bc47f9a920ab64e2c48ceeb8e092ff63c0f5f497baae4b6785c4ee655e6bd6\
+             031c3c69c62fba175d0614ed1e9de4587c885d200a6850a283c7224625a56d23\
+             76b349101afbb077"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_writes_keep_permissions() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = env::temp_dir().join(format!("git-signify-atomic-writes-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        // temporary files left behind by interrupted writes are ignored
+        let path = dir.join("hook");
+        fs::write(dir.join(".hook.tmp"), "stale").unwrap();
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+        write_file_atomically(&path, b"new", false).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(mode(&path), 0o750);
+
+        let path = dir.join("key.sec");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_file_atomically(&path, b"new", true).unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn signer_and_oid_must_be_canonical() {
+        let signer = "0123456789abcdef0123456789abcdef01234567";
+        let oid = "89abcdef0123456789abcdef0123456789abcdef";
+        let parsed = parse_signer_and_oid(&format!("{signer}/{oid}")).unwrap();
+        assert_eq!(parsed.0.to_string(), signer);
+        assert_eq!(parsed.1.to_string(), oid);
+
+        for invalid in [
+            format!("{}/{oid}", &signer[..7]),
+            format!("{signer}/{}", &oid[..39]),
+            format!("{}/{oid}", signer.to_uppercase()),
+            format!("{signer}/{oid}0"),
+            format!("{signer}/{oid}/extra"),
+        ] {
+            assert!(parse_signer_and_oid(&invalid).is_none(), "{invalid}");
+        }
+    }
+}
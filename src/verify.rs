@@ -1,30 +1,345 @@
 //! Verify signatures stored under git references
 //! with [`libsignify`].
 
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
+use git2::Repository;
+use serde::Serialize;
 
-use crate::raw::verify::verify;
-use crate::utils;
+use crate::attest;
+use crate::countersign;
+use crate::output::{self, Format, KeyInfo, ObjectInfo, VerificationStatus};
+use crate::utils::{self, PublicKey};
 
-/// Execute the `verify` command.
-pub fn command(key_path: PathBuf, rev: String) -> Result<()> {
+/// Results of the `verify` command, as described in JSON output.
+#[derive(Serialize)]
+struct Verification {
+    /// The verified object.
+    object: ObjectInfo,
+    /// The reference naming the verified object, when verifying refs
+    /// matching a glob.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refname: Option<String>,
+    /// Minimum number of keys whose signature must verify.
+    threshold: Option<usize>,
+    /// Number of distinct keys whose signature verified, counting keys
+    /// stored in both the signify and minisign formats once.
+    verified_keys: usize,
+    /// Verification results of each key.
+    keys: Vec<KeyVerification>,
+    /// Verification results of each required attestation type.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attestations: Vec<AttestationVerification>,
+}
+
+/// Verification result of some attestation type, as described in JSON
+/// output.
+#[derive(Serialize)]
+struct AttestationVerification {
+    /// The required attestation type.
+    #[serde(rename = "type")]
+    kind: String,
+    /// The keys whose attestation of that type verified.
+    attested_by: Vec<PathBuf>,
+}
+
+/// Verification result of some key, as described in JSON output.
+#[derive(Serialize)]
+struct KeyVerification {
+    /// The key signatures were verified with.
+    key: KeyInfo,
+    /// The outcome of the verification.
+    status: VerificationStatus,
+    /// The reference of the verified signature, if any was found.
+    signature_ref: Option<String>,
+    /// The reason why verification failed, if it did.
+    error: Option<String>,
+    /// The witness keys which countersigned the signature, when
+    /// countersignatures are required.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    countersigned_by: Vec<PathBuf>,
+}
+
+impl Verification {
+    /// Number of keys whose signature failed to verify.
+    fn invalid_keys(&self) -> usize {
+        self.keys
+            .iter()
+            .filter(|key| key.status == VerificationStatus::Invalid)
+            .count()
+    }
+
+    /// Required attestation types which no key attested.
+    fn unattested(&self) -> Vec<&str> {
+        self.attestations
+            .iter()
+            .filter(|attestation| attestation.attested_by.is_empty())
+            .map(|attestation| attestation.kind.as_str())
+            .collect()
+    }
+
+    /// Whether fewer than `threshold` keys verified the object.
+    fn below_threshold(&self) -> bool {
+        self.threshold
+            .is_some_and(|threshold| self.verified_keys < threshold)
+    }
+}
+
+/// Execute the `verify` command, over `rev`, or over the target of
+/// every reference matching `refs_glob`. If a `threshold` is given,
+/// fail unless at least that many keys verify each signed revision.
+/// If `witness_path` is given, signatures only verify if one of the
+/// keys at that path countersigned them. Each of the `attestations`
+/// types must be attested by at least one key.
+pub fn command(
+    key_path: PathBuf,
+    threshold: Option<usize>,
+    witness_path: Option<PathBuf>,
+    attestations: Vec<String>,
+    rev: Option<String>,
+    refs_glob: Option<String>,
+    format: Format,
+) -> Result<()> {
     let repo = utils::open_repository()?;
-    for (path, public_key) in utils::get_public_keys(key_path)? {
-        let tree_rev = {
-            let object_oid = repo
-                .revparse_single(&rev)
-                .context("Failed to look-up git object")?
-                .id();
-            let key_fingerprint = public_key.fingerprint()?;
-            utils::craft_signature_reference(key_fingerprint, object_oid)
-        };
-        if verify(&repo, &public_key, &tree_rev, false)?.is_right() {
-            println!("Signature verified successfully with {}", path.display());
-        } else {
-            println!("No signature found for key {}", path.display());
+    let public_keys = utils::get_public_keys(key_path)?;
+    let witnesses = witness_path.map(utils::get_public_keys).transpose()?;
+    let policy = Policy {
+        public_keys: &public_keys,
+        witnesses: witnesses.as_ref(),
+        attestations: &attestations,
+    };
+
+    let Some(refs_glob) = refs_glob else {
+        let rev = rev.context("No revision to verify")?;
+        let verification =
+            verify_revision(&repo, &policy, threshold, &rev, None, !format.is_json())?;
+        if format.is_json() {
+            output::print_json("verify", &verification)?;
         }
+        let invalid_keys = verification.invalid_keys();
+        anyhow::ensure!(
+            invalid_keys == 0,
+            "Found {invalid_keys} invalid signatures over {rev}"
+        );
+        if let Some(threshold) = threshold {
+            anyhow::ensure!(
+                !verification.below_threshold(),
+                "Only {} out of the required {threshold} keys verified {rev}",
+                verification.verified_keys
+            );
+        }
+        let unattested = verification.unattested();
+        anyhow::ensure!(
+            unattested.is_empty(),
+            "Missing attestations {} over {rev}",
+            unattested.join(", ")
+        );
+        return Ok(());
+    };
+
+    let mut verifications = Vec::new();
+    for refname in utils::matching_refs(&repo, &refs_glob)? {
+        verifications.push(verify_revision(
+            &repo,
+            &policy,
+            threshold,
+            &refname,
+            Some(&refname),
+            false,
+        )?);
     }
+
+    let invalid_refs = verifications
+        .iter()
+        .filter(|verification| verification.invalid_keys() > 0)
+        .count();
+    let unverified_refs = verifications
+        .iter()
+        .filter(|verification| verification.below_threshold())
+        .count();
+    let unattested_refs = verifications
+        .iter()
+        .filter(|verification| !verification.unattested().is_empty())
+        .count();
+
+    if format.is_json() {
+        output::print_json("verify", &verifications)?;
+    } else if verifications.is_empty() {
+        println!("No refs match {refs_glob}");
+    } else {
+        let rows: Vec<_> = verifications
+            .iter()
+            .map(|verification| {
+                let status = if verification.invalid_keys() > 0 {
+                    "invalid"
+                } else if verification.below_threshold() || verification.verified_keys == 0 {
+                    "unverified"
+                } else if !verification.unattested().is_empty() {
+                    "unattested"
+                } else {
+                    "valid"
+                };
+                [
+                    verification.refname.clone().unwrap_or_default(),
+                    verification.object.oid[..7].to_owned(),
+                    format!("{}/{}", verification.verified_keys, verification.keys.len()),
+                    status.to_owned(),
+                ]
+            })
+            .collect();
+        output::print_table(["REF", "OBJECT", "KEYS", "STATUS"], &rows);
+    }
+
+    anyhow::ensure!(
+        invalid_refs == 0,
+        "Found invalid signatures over {invalid_refs} refs"
+    );
+    if let Some(threshold) = threshold {
+        anyhow::ensure!(
+            unverified_refs == 0,
+            "Fewer than the required {threshold} keys verified {unverified_refs} refs"
+        );
+    }
+    anyhow::ensure!(
+        unattested_refs == 0,
+        "Missing required attestations over {unattested_refs} refs"
+    );
     Ok(())
 }
+
+/// Keys and requirements signatures are verified against.
+struct Policy<'policy> {
+    /// Keys whose signatures are verified.
+    public_keys: &'policy BTreeMap<PathBuf, PublicKey>,
+    /// Keys of the witnesses, one of which must countersign each
+    /// signature, if any were given.
+    witnesses: Option<&'policy BTreeMap<PathBuf, PublicKey>>,
+    /// Types of attestations required over verified objects.
+    attestations: &'policy [String],
+}
+
+/// Verify the signatures of each of the public keys of `policy` over
+/// `rev`, along with the attestations it requires. Results are printed
+/// if `verbose` is set.
+fn verify_revision(
+    repo: &Repository,
+    policy: &Policy<'_>,
+    threshold: Option<usize>,
+    rev: &str,
+    refname: Option<&str>,
+    verbose: bool,
+) -> Result<Verification> {
+    let object_oid = repo
+        .revparse_single(rev)
+        .context("Failed to look-up git object")?
+        .id();
+    let mut key_verifications = Vec::new();
+    let mut signers = HashSet::new();
+    for (path, public_key) in policy.public_keys {
+        let mut status = VerificationStatus::Missing;
+        let mut signature_ref = None;
+        let mut error = None;
+        let mut countersigned_by = Vec::new();
+        // signatures may have been made with the same key, in another format
+        for key_fingerprint in public_key.equivalent_fingerprints()? {
+            let tree_rev = utils::craft_signature_reference(key_fingerprint, object_oid);
+            let Some(signature_oid) = utils::revparse_single_ok_or_else(
+                repo,
+                &tree_rev,
+                |obj| Ok(Some(obj.id())),
+                || Ok(None),
+            )?
+            else {
+                continue;
+            };
+            signature_ref = Some(tree_rev);
+            if let Err(e) =
+                utils::verify_signature_over(repo, public_key, signature_oid, object_oid)
+            {
+                status = VerificationStatus::Invalid;
+                error = Some(format!("{e:#}"));
+                continue;
+            }
+            if let Some(witnesses) = policy.witnesses {
+                countersigned_by = countersign::verify_countersignatures(
+                    repo,
+                    witnesses,
+                    key_fingerprint,
+                    object_oid,
+                    signature_oid,
+                )?;
+                if countersigned_by.is_empty() {
+                    status = VerificationStatus::Invalid;
+                    error = Some("Signature is not countersigned by a witness".to_owned());
+                    continue;
+                }
+            }
+            status = VerificationStatus::Valid;
+            error = None;
+            break;
+        }
+        if verbose {
+            match status {
+                VerificationStatus::Valid => {
+                    println!("Signature verified successfully with {}", path.display());
+                    if !countersigned_by.is_empty() {
+                        println!("Countersigned by witnesses:");
+                        for witness in &countersigned_by {
+                            println!("  - {}", witness.display());
+                        }
+                    }
+                }
+                VerificationStatus::Missing => {
+                    println!("No signature found for key {}", path.display());
+                }
+                VerificationStatus::Invalid => println!(
+                    "Invalid signature stored under {}: {}",
+                    signature_ref.as_deref().unwrap_or_default(),
+                    error.as_deref().unwrap_or_default(),
+                ),
+            }
+        }
+        if status == VerificationStatus::Valid {
+            signers.insert(public_key.identity()?);
+        }
+        key_verifications.push(KeyVerification {
+            key: KeyInfo::new(path.clone(), public_key)?,
+            status,
+            signature_ref,
+            error,
+            countersigned_by,
+        });
+    }
+    let mut attestations = Vec::new();
+    for kind in policy.attestations {
+        let attested_by =
+            attest::attested_by(repo, policy.public_keys, kind, object_oid, |refname| {
+                repo.refname_to_id(refname).ok()
+            })?;
+        if verbose {
+            if attested_by.is_empty() {
+                println!("No valid {kind} attestation found");
+            }
+            for path in &attested_by {
+                println!(
+                    "Attestation {kind} verified successfully with {}",
+                    path.display()
+                );
+            }
+        }
+        attestations.push(AttestationVerification {
+            kind: kind.clone(),
+            attested_by: attested_by.into_iter().map(Path::to_path_buf).collect(),
+        });
+    }
+    Ok(Verification {
+        object: ObjectInfo::new(repo, object_oid),
+        refname: refname.map(str::to_owned),
+        threshold,
+        verified_keys: signers.len(),
+        keys: key_verifications,
+        attestations,
+    })
+}